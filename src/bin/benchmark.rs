@@ -0,0 +1,216 @@
+//! Plays generated boards with the solver, without connecting to Discord.
+//!
+//! Usage: benchmark [easy|medium|hard|WIDTHxHEIGHTxMINES] [boards] [--seed N] [--policy POLICY]
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use minesweeper::game::{self, Game, SafetyPolicy};
+use minesweeper::solver::{self, DeductionLevel};
+use rand::{rngs::StdRng, SeedableRng};
+
+struct Settings {
+    name: String,
+    game_settings: (u32, u32, u32),
+    boards: u32,
+    seed: u64,
+    safety_policy: SafetyPolicy,
+}
+
+fn parse_size(size: &str) -> Option<(u32, u32, u32)> {
+    let parts = size
+        .split('x')
+        .map(|part| part.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts.as_slice() {
+        [width, height, mines] if *width > 0 && *height > 0 => Some((*width, *height, *mines)),
+        _ => None,
+    }
+}
+
+fn parse_settings(args: &[String]) -> Option<Settings> {
+    let mut settings = Settings {
+        name: "medium".to_string(),
+        game_settings: game::difficulty_settings("medium").unwrap(),
+        boards: 1000,
+        seed: 0,
+        safety_policy: SafetyPolicy::default(),
+    };
+    let mut positional = 0;
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--seed" => settings.seed = args.next()?.parse().ok()?,
+            "--policy" => settings.safety_policy = SafetyPolicy::parse(args.next()?)?,
+            _ if positional == 0 => {
+                settings.game_settings =
+                    game::difficulty_settings(arg).or_else(|| parse_size(arg))?;
+                settings.name = arg.clone();
+                positional += 1;
+            }
+            _ if positional == 1 => {
+                settings.boards = arg.parse().ok().filter(|boards| *boards > 0)?;
+                positional += 1;
+            }
+            _ => return None,
+        }
+    }
+
+    Some(settings)
+}
+
+fn per_second(count: u64, time: Duration) -> f64 {
+    count as f64 / time.as_secs_f64().max(f64::EPSILON)
+}
+
+fn percentage(count: u32, total: u32) -> f64 {
+    count as f64 * 100.0 / total as f64
+}
+
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    let settings = match parse_settings(&args) {
+        Some(settings) => settings,
+        None => {
+            eprintln!("Usage: benchmark [easy|medium|hard|WIDTHxHEIGHTxMINES] [boards] [--seed N] [--policy nosafety|safetile|opening|radius=N]");
+            std::process::exit(2);
+        }
+    };
+
+    let (width, height, number_of_mines) = settings.game_settings;
+    let mut rng = StdRng::seed_from_u64(settings.seed);
+
+    let mut wins = 0;
+    let mut guesses = BTreeMap::<u32, u32>::new();
+    let mut hardest = BTreeMap::<Option<DeductionLevel>, u32>::new();
+    let mut three_bvs = vec![];
+
+    let mut generation_time = Duration::ZERO;
+    let mut reveal_time = Duration::ZERO;
+    let mut revealed_tiles = 0u64;
+    let mut solve_time = Duration::ZERO;
+
+    for board in 0..settings.boards {
+        let mut game = Game::new(width, height, number_of_mines);
+        game.safety_policy = settings.safety_policy;
+
+        let started = Instant::now();
+        let first_dig = match game.start_seeded(settings.seed.wrapping_add(board as u64)) {
+            Ok(first_dig) => first_dig,
+            Err(e) => {
+                eprintln!("Couldn't generate the board: {}", e);
+                std::process::exit(1);
+            }
+        };
+        generation_time += started.elapsed();
+
+        three_bvs.push(solver::three_bv(&game));
+
+        // Digs every safe tile of a copy of the board, to time revealing on its own.
+        let mut copy = Game::new(width, height, number_of_mines);
+        copy.start_with_layout(&game.tiles, first_dig);
+
+        let started = Instant::now();
+        for y in 0..height {
+            for x in 0..width {
+                let tile = &copy.tiles[y as usize][x as usize];
+                if tile.is_mine || tile.is_revealed {
+                    continue;
+                }
+                if let Ok(outcome) = copy.dig((x, y)) {
+                    revealed_tiles += outcome.revealed.len() as u64;
+                }
+            }
+        }
+        reveal_time += started.elapsed();
+
+        let started = Instant::now();
+        let report = solver::solve(&mut game, &mut rng);
+        solve_time += started.elapsed();
+
+        if report.won {
+            wins += 1;
+        }
+        *guesses.entry(report.guesses).or_default() += 1;
+        *hardest.entry(report.hardest).or_default() += 1;
+    }
+
+    let boards = settings.boards;
+    three_bvs.sort_unstable();
+
+    println!(
+        "{} boards of {} ({} by {} with {} mines, {}, seed {})",
+        boards,
+        settings.name,
+        width,
+        height,
+        number_of_mines,
+        settings.safety_policy,
+        settings.seed
+    );
+    println!();
+    println!(
+        "Win rate: {:.1}% ({} of {})",
+        percentage(wins, boards),
+        wins,
+        boards
+    );
+
+    println!();
+    println!("Forced guesses:");
+    for (count, games) in &guesses {
+        println!(
+            "  {:>3}: {:>7} ({:.1}%)",
+            count,
+            games,
+            percentage(*games, boards)
+        );
+    }
+
+    println!();
+    println!("Hardest deduction:");
+    for (level, games) in &hardest {
+        let level = match level {
+            None => "None",
+            Some(DeductionLevel::Single) => "Single",
+            Some(DeductionLevel::Pair) => "Pair",
+            Some(DeductionLevel::Exhaustive) => "Exhaustive",
+        };
+        println!(
+            "  {:>10}: {:>7} ({:.1}%)",
+            level,
+            games,
+            percentage(*games, boards)
+        );
+    }
+
+    println!();
+    println!(
+        "3BV: min {}, median {}, mean {:.1}, max {}",
+        three_bvs[0],
+        three_bvs[three_bvs.len() / 2],
+        three_bvs.iter().map(|v| *v as f64).sum::<f64>() / boards as f64,
+        three_bvs[three_bvs.len() - 1]
+    );
+
+    println!();
+    println!(
+        "Generation: {:.0} boards/s ({:.1} µs per board, including the first dig)",
+        per_second(boards as u64, generation_time),
+        generation_time.as_secs_f64() * 1e6 / boards as f64
+    );
+    println!(
+        "Reveal: {:.0} tiles/s ({} tiles in {:.1} ms)",
+        per_second(revealed_tiles, reveal_time),
+        revealed_tiles,
+        reveal_time.as_secs_f64() * 1e3
+    );
+    println!(
+        "Solver: {:.0} boards/s ({:.1} ms in total)",
+        per_second(boards as u64, solve_time),
+        solve_time.as_secs_f64() * 1e3
+    );
+}
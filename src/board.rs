@@ -0,0 +1,474 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serenity::prelude::TypeMapKey;
+use tiny_skia::Pixmap;
+
+use crate::coop::CoopGame;
+use crate::drawing::{DefaultMinesweeperDrawer, MinesweeperDrawer};
+use crate::flags::FlagsGame;
+use crate::game::{Game, GameState, GenerationError, MoveError, MoveOutcome, SafetyPolicy};
+use crate::solver::Tier;
+
+/// The embed sent once a game is over.
+pub struct Summary {
+    pub title: String,
+    pub description: String,
+    /// The name and value of each field, and whether it's shown inline.
+    pub fields: Vec<(String, String, bool)>,
+}
+
+impl Summary {
+    /// How a single player game ended, how long it took and what it was played with.
+    pub fn classic(game: &Game) -> Self {
+        let difference = game.active_time();
+        let minutes = difference.num_minutes();
+        let seconds = difference.num_seconds() - difference.num_minutes() * 60;
+
+        let mut summary = Summary {
+            title: "Game Summary".to_string(),
+            description: format!(
+                "Game {} in {} minute{} and {} second{}",
+                if game.state == GameState::Won {
+                    "won"
+                } else {
+                    "lost"
+                },
+                minutes,
+                if minutes == 1 { "" } else { "s" },
+                seconds,
+                if seconds == 1 { "" } else { "s" }
+            ),
+            fields: vec![
+                (
+                    "Grid Size".to_string(),
+                    format!("{} by {}", game.width, game.height),
+                    true,
+                ),
+                (
+                    "Mine Count".to_string(),
+                    game.number_of_mines.to_string(),
+                    true,
+                ),
+                (
+                    "First Dig".to_string(),
+                    game.safety_policy.to_string(),
+                    true,
+                ),
+            ],
+        };
+
+        if let Some(rating) = game.rating {
+            let value = match game.requested_tier {
+                Some(tier) if tier != rating.tier => {
+                    format!("{} (asked for {})", rating, tier)
+                }
+                _ => rating.to_string(),
+            };
+            summary.fields.push(("Rating".to_string(), value, true));
+        }
+
+        if game.pause_count > 0 {
+            summary.fields.push((
+                "Paused".to_string(),
+                format!(
+                    "{} time{}",
+                    game.pause_count,
+                    if game.pause_count == 1 { "" } else { "s" }
+                ),
+                true,
+            ));
+        }
+
+        summary
+    }
+}
+
+/// What the players can see of one tile.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct TileView {
+    pub is_revealed: bool,
+    pub is_flagged: bool,
+    /// The number shown on a revealed tile.
+    pub adjacent_mines: u32,
+}
+
+/// The rules of a game mode. Commands only ever talk to boards through this trait, so a new mode
+/// needs an implementation and an entry in [`VARIANTS`] rather than its own handlers. Nothing
+/// here assumes the board is a classic rectangular [`Game`]; modes played on one can hand it out
+/// through [`Board::game`] for the features that only exist on classic boards.
+pub trait Board: Send + Sync {
+    /// The classic game the board is played on, for modes that have one. Only races, the daily
+    /// challenge and first-dig generation use it.
+    fn game(&self) -> Option<&Game> {
+        None
+    }
+
+    fn game_mut(&mut self) -> Option<&mut Game> {
+        None
+    }
+
+    fn state(&self) -> GameState;
+
+    /// The width and height of the grid that coordinates are given in.
+    fn dimensions(&self) -> (u32, u32);
+
+    fn number_of_mines(&self) -> u32;
+
+    /// What the players can see of a tile, or None if the position isn't on the board.
+    fn tile(&self, position: (u32, u32)) -> Option<TileView>;
+
+    /// The tiles around a position, which its number counts and chording digs.
+    fn neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)>;
+
+    fn time_started(&self) -> DateTime<Utc>;
+
+    /// The last time someone did something that keeps the game alive.
+    fn last_activity(&self) -> DateTime<Utc>;
+
+    /// Whether columns are labelled with letters instead of numbers.
+    fn letter_columns(&self) -> bool;
+
+    fn set_letter_columns(&mut self, letter_columns: bool);
+
+    /// The channel and id of the message the board was last shown in, which later moves edit.
+    fn board_message(&self) -> Option<(u64, u64)>;
+
+    fn set_board_message(&mut self, message: Option<(u64, u64)>);
+
+    fn is_finished(&self) -> bool {
+        matches!(self.state(), GameState::Won | GameState::Lost)
+    }
+
+    fn dig(&mut self, _player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.game_mut().ok_or(MoveError::Unsupported)?.dig(position)
+    }
+
+    fn flag(&mut self, _player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.game_mut()
+            .ok_or(MoveError::Unsupported)?
+            .flag(position)
+    }
+
+    fn unflag(&mut self, _player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.game_mut()
+            .ok_or(MoveError::Unsupported)?
+            .unflag(position)
+    }
+
+    /// Digs every unflagged tile around a number that has as many flags around it as mines.
+    /// Modes without a classic game get this from their tiles, neighbours and digs.
+    fn chord(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if let Some(game) = self.game_mut() {
+            return game.chord(position);
+        }
+
+        let tile = self.tile(position).ok_or(MoveError::OutOfBounds)?;
+        if !tile.is_revealed || tile.adjacent_mines == 0 {
+            return Err(MoveError::NotANumber);
+        }
+
+        let around = self.neighbours(position);
+        let flags = around
+            .iter()
+            .filter_map(|neighbour| self.tile(*neighbour))
+            .filter(|neighbour| neighbour.is_flagged)
+            .count() as u32;
+        if flags != tile.adjacent_mines {
+            return Err(MoveError::NotSatisfied);
+        }
+
+        let mut outcome = MoveOutcome::default();
+        for neighbour in around {
+            if self
+                .tile(neighbour)
+                .is_some_and(|tile| tile.is_revealed || tile.is_flagged)
+            {
+                continue;
+            }
+
+            let dug = self.dig(player, neighbour)?;
+            outcome.revealed.extend(dug.revealed);
+            if dug.new_state.is_some() {
+                outcome.new_state = dug.new_state;
+                break;
+            }
+        }
+
+        Ok(outcome)
+    }
+
+    fn undo(&mut self, _player: u64) -> Result<(), MoveError> {
+        self.game_mut().ok_or(MoveError::Unsupported)?.undo()
+    }
+
+    /// Digs whatever the player's flags already make safe, for players who asked for it after
+    /// every dig. Returns the revealed tiles.
+    fn dig_satisfied_tiles(&mut self, _player: u64) -> Vec<(u32, u32)> {
+        self.game_mut()
+            .map(|game| game.dig_satisfied_tiles())
+            .unwrap_or_default()
+    }
+
+    fn pause(&mut self, _player: u64) -> Result<(), MoveError> {
+        self.game_mut().ok_or(MoveError::Unsupported)?.pause()
+    }
+
+    fn resume(&mut self, _player: u64) -> Result<(), MoveError> {
+        self.game_mut().ok_or(MoveError::Unsupported)?.resume()
+    }
+
+    /// Adds a player to a running game. Returns None if this mode can't be joined, and false if
+    /// they had already joined.
+    fn join(&mut self, _player: u64, _name: &str) -> Option<bool> {
+        None
+    }
+
+    fn can_stop(&self, _player: u64) -> bool {
+        true
+    }
+
+    /// Sent to the channel when the game starts.
+    fn introduction(&self) -> Option<String> {
+        None
+    }
+
+    /// Sent to the channel after a move that didn't end the game.
+    fn announcement(&self, _outcome: &MoveOutcome) -> Option<String> {
+        None
+    }
+
+    fn render(&self) -> Pixmap;
+
+    /// Draws the board for people watching it, who mustn't learn anything the players can't see.
+    fn render_for_spectators(&self) -> Pixmap;
+
+    fn summary(&self) -> Summary;
+}
+
+/// What players can see of a tile of a classic game.
+pub fn classic_tile(game: &Game, position: (u32, u32)) -> Option<TileView> {
+    let tile = game
+        .tiles
+        .get(position.1 as usize)?
+        .get(position.0 as usize)?;
+
+    Some(TileView {
+        is_revealed: tile.is_revealed,
+        is_flagged: tile.is_flagged,
+        adjacent_mines: if tile.is_revealed {
+            tile.adjacent_mines
+        } else {
+            0
+        },
+    })
+}
+
+impl Board for Game {
+    fn game(&self) -> Option<&Game> {
+        Some(self)
+    }
+
+    fn game_mut(&mut self) -> Option<&mut Game> {
+        Some(self)
+    }
+
+    fn state(&self) -> GameState {
+        self.state
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn number_of_mines(&self) -> u32 {
+        self.number_of_mines
+    }
+
+    fn tile(&self, position: (u32, u32)) -> Option<TileView> {
+        classic_tile(self, position)
+    }
+
+    fn neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        Game::neighbours(self, position)
+    }
+
+    fn time_started(&self) -> DateTime<Utc> {
+        self.time_started
+    }
+
+    fn last_activity(&self) -> DateTime<Utc> {
+        Game::last_activity(self)
+    }
+
+    fn letter_columns(&self) -> bool {
+        self.letter_columns
+    }
+
+    fn set_letter_columns(&mut self, letter_columns: bool) {
+        self.letter_columns = letter_columns;
+    }
+
+    fn board_message(&self) -> Option<(u64, u64)> {
+        self.board_message
+    }
+
+    fn set_board_message(&mut self, message: Option<(u64, u64)>) {
+        self.board_message = message;
+    }
+
+    fn render(&self) -> Pixmap {
+        DefaultMinesweeperDrawer::draw_board(self)
+    }
+
+    fn render_for_spectators(&self) -> Pixmap {
+        DefaultMinesweeperDrawer::draw_board(&self.spectator_view())
+    }
+
+    fn summary(&self) -> Summary {
+        Summary::classic(self)
+    }
+}
+
+/// Who a running game belongs to. Single player games belong to their player in the channel they
+/// were started in, and can be named so one player can run several in the same channel. Shared
+/// games belong to the channel.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GameKey {
+    Player {
+        player: u64,
+        channel: u64,
+        name: Option<String>,
+    },
+    Channel(u64),
+}
+
+impl GameKey {
+    pub fn player(player: u64, channel: u64, name: Option<&str>) -> GameKey {
+        GameKey::Player {
+            player,
+            channel,
+            name: name.map(str::to_string),
+        }
+    }
+
+    /// The player a single player game belongs to.
+    pub fn owner(&self) -> Option<u64> {
+        match self {
+            GameKey::Player { player, .. } => Some(*player),
+            GameKey::Channel(_) => None,
+        }
+    }
+
+    pub fn channel(&self) -> u64 {
+        match self {
+            GameKey::Player { channel, .. } | GameKey::Channel(channel) => *channel,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            GameKey::Player { name, .. } => name.as_deref(),
+            GameKey::Channel(_) => None,
+        }
+    }
+}
+
+pub struct GameDataKey;
+
+impl TypeMapKey for GameDataKey {
+    type Value = Arc<DashMap<GameKey, Box<dyn Board>>>;
+}
+
+/// The game each player last started, joined or switched to, keyed by player and channel.
+pub struct FocusDataKey;
+
+impl TypeMapKey for FocusDataKey {
+    type Value = Arc<DashMap<(u64, u64), GameKey>>;
+}
+
+/// Everything a variant needs to create its board.
+pub struct GameSetup {
+    pub game_settings: (u32, u32, u32),
+    pub safety_policy: SafetyPolicy,
+    pub requested_tier: Option<Tier>,
+    /// The id and name of every starting player, beginning with whoever started the game.
+    pub players: Vec<(u64, String)>,
+}
+
+pub struct Variant {
+    pub name: &'static str,
+    pub usage: &'static str,
+    /// How many players the game starts with, including whoever started it.
+    pub players: usize,
+    /// Whether the game belongs to the channel rather than to the player who started it.
+    pub shared: bool,
+    pub create: fn(&GameSetup) -> Result<Box<dyn Board>, GenerationError>,
+}
+
+/// Every game mode that can be started with startgame. The first one is used when no mode is
+/// given.
+pub const VARIANTS: &[Variant] = &[
+    Variant {
+        name: "classic",
+        usage: "startgame [difficulty]",
+        players: 1,
+        shared: false,
+        create: create_classic,
+    },
+    Variant {
+        name: "coop",
+        usage: "startgame coop [difficulty]",
+        players: 1,
+        shared: true,
+        create: create_coop,
+    },
+    Variant {
+        name: "flagstorm",
+        usage: "startgame flagstorm @user [difficulty]",
+        players: 2,
+        shared: true,
+        create: create_flagstorm,
+    },
+];
+
+pub fn find_variant(name: &str) -> Option<&'static Variant> {
+    VARIANTS.iter().find(|variant| variant.name == name)
+}
+
+fn new_validated_game(setup: &GameSetup) -> Result<Game, GenerationError> {
+    let (width, height, number_of_mines) = setup.game_settings;
+
+    let mut game = Game::new(width, height, number_of_mines);
+    game.safety_policy = setup.safety_policy;
+    game.requested_tier = setup.requested_tier;
+    game.validate()?;
+
+    Ok(game)
+}
+
+fn create_classic(setup: &GameSetup) -> Result<Box<dyn Board>, GenerationError> {
+    Ok(Box::new(new_validated_game(setup)?))
+}
+
+fn create_coop(setup: &GameSetup) -> Result<Box<dyn Board>, GenerationError> {
+    let (owner, owner_name) = &setup.players[0];
+
+    Ok(Box::new(CoopGame::new(
+        new_validated_game(setup)?,
+        *owner,
+        owner_name,
+    )))
+}
+
+fn create_flagstorm(setup: &GameSetup) -> Result<Box<dyn Board>, GenerationError> {
+    let (first, first_name) = &setup.players[0];
+    let (second, second_name) = &setup.players[1];
+
+    Ok(Box::new(FlagsGame::new(
+        setup.game_settings,
+        [*first, *second],
+        [first_name.clone(), second_name.clone()],
+    )?))
+}
@@ -0,0 +1,188 @@
+use std::fmt;
+
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use serenity::builder::CreateComponents;
+use serenity::http::request::RequestBuilder;
+use serenity::http::routing::RouteInfo;
+use serenity::http::{Http, HttpError};
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::prelude::TypeMap;
+
+use crate::board::{Board, GameDataKey, GameKey};
+use crate::controls;
+use crate::drawing;
+use crate::guilds::GuildConfigDataKey;
+
+/// Why a board couldn't be shown.
+#[derive(Debug)]
+pub enum BoardMessageError {
+    /// The message to edit was deleted, or the bot can't see or edit it anymore.
+    Gone,
+    Edit(serenity::Error),
+    Send(serenity::Error),
+}
+
+impl fmt::Display for BoardMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardMessageError::Gone => write!(f, "the board message is gone"),
+            BoardMessageError::Edit(e) => write!(f, "couldn't edit the board: {}", e),
+            BoardMessageError::Send(e) => write!(f, "couldn't send the board: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for BoardMessageError {}
+
+/// Everything needed to show a board, taken while the board is held so the guard into the game
+/// map can be dropped before anything is sent.
+pub struct BoardRender {
+    pub image: Vec<u8>,
+    /// The controls under the board, which finished games don't get.
+    pub components: Option<CreateComponents>,
+    /// The message the board was last shown in.
+    pub message: Option<(u64, u64)>,
+}
+
+impl BoardRender {
+    pub fn new(board: &dyn Board) -> Self {
+        BoardRender {
+            image: drawing::scale_to_tile_size(board.render())
+                .encode_png()
+                .unwrap(),
+            components: (!board.is_finished()).then(|| controls::components(board)),
+            message: board.board_message(),
+        }
+    }
+}
+
+/// Shows a game after it changed. The message the board was last shown in is edited in place,
+/// unless `repost` is set, the server prefers reposting, or that message is gone or in another
+/// channel. A reposted board becomes the message later moves edit. Any other failure to edit is
+/// returned rather than reposted, so a rate limited bot doesn't double its traffic.
+///
+/// The board is only held while it's drawn and while the new message is stored, never while
+/// waiting on Discord.
+pub async fn show_board(
+    http: &Http,
+    data: &TypeMap,
+    guild: Option<GuildId>,
+    channel: ChannelId,
+    key: &GameKey,
+    repost: bool,
+) -> Result<(), BoardMessageError> {
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let render = match game_data.get(key) {
+        Some(board) => BoardRender::new(&**board),
+        None => return Ok(()),
+    };
+
+    let repost = repost
+        || guild.is_some_and(|guild| {
+            data.get::<GuildConfigDataKey>()
+                .unwrap()
+                .get(guild.0)
+                .repost_boards
+        });
+
+    if let Some((message_channel, message)) = render.message {
+        if !repost && message_channel == channel.0 {
+            let components = render
+                .components
+                .as_ref()
+                .map(|components| components.0.clone())
+                .unwrap_or_default();
+
+            let edited =
+                edit_message_image(http, channel, message, render.image.clone(), components).await;
+
+            match edited {
+                Err(BoardMessageError::Gone) => (),
+                result => return result,
+            }
+        }
+    }
+
+    let message = crate::send_game_render(http, channel, render)
+        .await
+        .map_err(BoardMessageError::Send)?;
+
+    if let Some(mut board) = game_data.get_mut(key) {
+        board.set_board_message(Some((channel.0, message.id.0)));
+    }
+    Ok(())
+}
+
+/// Replaces the image and components of a message the bot sent. Serenity can't change a
+/// message's attachments, so the multipart body is built here, but the request still goes
+/// through serenity's ratelimiter.
+pub async fn edit_message_image(
+    http: &Http,
+    channel: ChannelId,
+    message: u64,
+    image: Vec<u8>,
+    components: Vec<Value>,
+) -> Result<(), BoardMessageError> {
+    let payload = json!({
+        "attachments": [{ "id": 0, "filename": "File.png" }],
+        "components": components,
+    })
+    .to_string();
+
+    let boundary = format!("minesweeper{:016x}", rand::random::<u64>());
+    let body = multipart_body(&boundary, &payload, &image);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_str(&format!("multipart/form-data; boundary={}", boundary)).unwrap(),
+    );
+
+    let mut request = RequestBuilder::new(RouteInfo::EditMessage {
+        channel_id: channel.0,
+        message_id: message,
+    });
+    request.body(Some(&body)).headers(Some(headers));
+
+    match http.request(request.build()).await {
+        Ok(_) => Ok(()),
+        Err(serenity::Error::Http(e))
+            if matches!(
+                e.as_ref(),
+                HttpError::UnsuccessfulRequest(response)
+                    if response.status_code == StatusCode::NOT_FOUND
+                        || response.status_code == StatusCode::FORBIDDEN
+            ) =>
+        {
+            Err(BoardMessageError::Gone)
+        }
+        Err(e) => Err(BoardMessageError::Edit(e)),
+    }
+}
+
+/// A multipart form with the message payload and the image as its first file.
+fn multipart_body(boundary: &str, payload: &str, image: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(image.len() + payload.len() + 256);
+
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"payload_json\"\r\nContent-Type: application/json\r\n\r\n{}\r\n",
+            boundary, payload
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"files[0]\"; filename=\"File.png\"\r\nContent-Type: image/png\r\n\r\n",
+            boundary
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(image);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    body
+}
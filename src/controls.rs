@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use serenity::builder::CreateComponents;
+use serenity::client::Context;
+use serenity::model::interactions::message_component::{ButtonStyle, MessageComponentInteraction};
+use serenity::model::interactions::{
+    InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+};
+use serenity::prelude::TypeMapKey;
+
+use crate::board::{Board, GameDataKey};
+use crate::guilds::GuildConfigDataKey;
+use crate::invocation::Invocation;
+use crate::moves::{self, MoveKind, PlannedMove};
+
+/// Select menus can't hold more options than this, so longer sides are split over two menus.
+/// Two menus for each side and the row of buttons are the five rows Discord allows under a
+/// message, which is enough for boards up to MAX_BOARD_SIDE.
+const MAX_SELECT_OPTIONS: u32 = 25;
+
+/// What a player has picked with the controls under a board. Coordinates are one-based.
+#[derive(Default)]
+pub struct Controls {
+    pub flag_mode: bool,
+    pub column: Option<u32>,
+    pub row: Option<u32>,
+    /// The last tile the controls were used on, which the chord button acts on.
+    pub last_target: Option<(u32, u32)>,
+}
+
+/// Each player's controls, keyed by player and channel like the game their moves go to.
+pub struct ControlDataKey;
+
+impl TypeMapKey for ControlDataKey {
+    type Value = Arc<DashMap<(u64, u64), Controls>>;
+}
+
+/// The buttons and menus sent under a running game's board.
+pub fn components(board: &dyn Board) -> CreateComponents {
+    let (width, height) = board.dimensions();
+    let mut components = CreateComponents::default();
+
+    add_select_menus(&mut components, "column", "Column", width, |x| {
+        column_label(board, x)
+    });
+    add_select_menus(&mut components, "row", "Row", height, |y| y.to_string());
+
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id("mode")
+                .label("Dig / Flag")
+                .style(ButtonStyle::Primary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("chord")
+                .label("Chord")
+                .style(ButtonStyle::Secondary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("undo")
+                .label("Undo")
+                .style(ButtonStyle::Secondary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("resend")
+                .label("Resend")
+                .style(ButtonStyle::Secondary)
+        })
+        .create_button(|button| {
+            button
+                .custom_id("stop")
+                .label("Stop")
+                .style(ButtonStyle::Danger)
+        })
+    });
+
+    components
+}
+
+/// Adds menus for picking one of `count` columns or rows, split into runs of MAX_SELECT_OPTIONS.
+/// Every menu's custom id starts with `id`, and its values are the one-based coordinates.
+fn add_select_menus(
+    components: &mut CreateComponents,
+    id: &str,
+    placeholder: &str,
+    count: u32,
+    label: impl Fn(u32) -> String,
+) {
+    let starts = (1..=count).step_by(MAX_SELECT_OPTIONS as usize);
+    let split = count > MAX_SELECT_OPTIONS;
+
+    for (menu_index, first) in starts.enumerate() {
+        let last = (first + MAX_SELECT_OPTIONS - 1).min(count);
+
+        components.create_action_row(|row| {
+            row.create_select_menu(|menu| {
+                menu.custom_id(format!("{}-{}", id, menu_index))
+                    .placeholder(if split {
+                        format!("{} {} to {}", placeholder, label(first), label(last))
+                    } else {
+                        placeholder.to_string()
+                    })
+                    .options(|options| {
+                        for value in first..=last {
+                            options.create_option(|option| option.label(label(value)).value(value));
+                        }
+                        options
+                    })
+            })
+        });
+    }
+}
+
+fn column_label(board: &dyn Board, x: u32) -> String {
+    if board.letter_columns() {
+        moves::column_name(x)
+    } else {
+        x.to_string()
+    }
+}
+
+/// Handles a click on the controls under a board. Picking a column and a row digs or flags that
+/// tile, depending on the player's mode, through the same code as the text commands.
+pub async fn handle(ctx: &Context, interaction: &MessageComponentInteraction) {
+    let invocation = Invocation {
+        author: interaction.user.clone(),
+        channel_id: interaction.channel_id,
+        guild_id: interaction.guild_id,
+        mentions: vec![],
+    };
+    let player = invocation.author.id.0;
+
+    let data = ctx.data.read().await;
+    let control_data = data.get::<ControlDataKey>().unwrap();
+
+    // Boards can outlive a server's change to its bot channels, so their controls are checked
+    // like slash commands are.
+    let allowed = data
+        .get::<GuildConfigDataKey>()
+        .unwrap()
+        .get_for(invocation.guild_id)
+        .allows_channel(invocation.channel_id.0);
+
+    let selected = interaction
+        .data
+        .values
+        .first()
+        .and_then(|value| value.parse::<u32>().ok());
+
+    let mut planned = None;
+    let mut reply = (!allowed).then_some("The bot can't be used in this channel!");
+
+    if allowed {
+        let mut controls = control_data
+            .entry((player, invocation.channel_id.0))
+            .or_default();
+
+        match interaction.data.custom_id.as_str() {
+            id if id.starts_with("column-") => controls.column = selected,
+            id if id.starts_with("row-") => controls.row = selected,
+            "mode" => {
+                controls.flag_mode = !controls.flag_mode;
+                reply = Some(if controls.flag_mode {
+                    "Picking a tile now flags it, or removes its flag."
+                } else {
+                    "Picking a tile now digs it."
+                });
+            }
+            "chord" => match controls.last_target {
+                Some(coordinates) => {
+                    planned = Some(PlannedMove {
+                        kind: MoveKind::Chord,
+                        coordinates,
+                    })
+                }
+                None => reply = Some("Pick a column and a row first!"),
+            },
+            _ => (),
+        }
+
+        if let (Some(column), Some(row)) = (controls.column, controls.row) {
+            let coordinates = (column, row);
+            let kind = if !controls.flag_mode {
+                MoveKind::Dig
+            } else if is_flagged(&data, &invocation, coordinates) {
+                MoveKind::Unflag
+            } else {
+                MoveKind::Flag
+            };
+
+            planned = Some(PlannedMove { kind, coordinates });
+            controls.column = None;
+            controls.row = None;
+            controls.last_target = Some(coordinates);
+        }
+    }
+
+    match reply {
+        Some(reply) => interaction
+            .create_interaction_response(&ctx.http, |response| {
+                response
+                    .kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|message| {
+                        message
+                            .content(reply)
+                            .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                    })
+            })
+            .await
+            .ok(),
+        None => interaction
+            .create_interaction_response(&ctx.http, |response| {
+                response.kind(InteractionResponseType::DeferredUpdateMessage)
+            })
+            .await
+            .ok(),
+    };
+
+    drop(data);
+
+    if !allowed {
+        return;
+    }
+
+    match interaction.data.custom_id.as_str() {
+        "undo" => crate::run_undo(ctx, &invocation).await,
+        "resend" => crate::run_resend(ctx, &invocation).await,
+        "stop" => crate::run_stopgame(ctx, &invocation).await,
+        _ => {
+            if let Some(planned) = planned {
+                crate::apply_moves(ctx, &invocation, &[planned], vec![]).await;
+            }
+        }
+    }
+}
+
+fn is_flagged(
+    data: &serenity::prelude::TypeMap,
+    invocation: &Invocation,
+    coordinates: (u32, u32),
+) -> bool {
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    crate::find_game_key(data, invocation.author.id.0, invocation.channel_id)
+        .and_then(|key| game_data.get(&key))
+        .and_then(|board| board.tile((coordinates.0 - 1, coordinates.1 - 1)))
+        .map(|tile| tile.is_flagged)
+        .unwrap_or(false)
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use tiny_skia::Pixmap;
+
+use crate::board::{classic_tile, Board, Summary, TileView};
+use crate::drawing::{DefaultMinesweeperDrawer, MinesweeperDrawer};
+use crate::game::{FlagChange, Game, GameState, MoveError, MoveOutcome};
+
+pub struct PlayerContribution {
+    pub user_id: u64,
+    pub name: String,
+    pub tiles_revealed: u32,
+}
+
+/// A game owned by a channel that every participant can dig and flag on.
+pub struct CoopGame {
+    pub game: Game,
+    pub owner: u64,
+    pub players: Vec<PlayerContribution>,
+    pub mine_hit_by: Option<u64>,
+    flagged_by: HashMap<(u32, u32), u64>,
+}
+
+impl CoopGame {
+    pub fn new(game: Game, owner: u64, owner_name: &str) -> Self {
+        CoopGame {
+            game,
+            owner,
+            players: vec![PlayerContribution {
+                user_id: owner,
+                name: owner_name.to_string(),
+                tiles_revealed: 0,
+            }],
+            mine_hit_by: None,
+            flagged_by: HashMap::new(),
+        }
+    }
+
+    pub fn is_player(&self, user_id: u64) -> bool {
+        self.players.iter().any(|p| p.user_id == user_id)
+    }
+
+    /// Adds a participant. Returns false if they had already joined.
+    pub fn join(&mut self, user_id: u64, name: &str) -> bool {
+        if self.is_player(user_id) {
+            return false;
+        }
+
+        self.players.push(PlayerContribution {
+            user_id,
+            name: name.to_string(),
+            tiles_revealed: 0,
+        });
+        true
+    }
+
+    pub fn dig(&mut self, user_id: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        let outcome = self.game.dig(position)?;
+        self.credit(user_id, &outcome);
+        Ok(outcome)
+    }
+
+    pub fn chord(&mut self, user_id: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        let outcome = self.game.chord(position)?;
+        self.credit(user_id, &outcome);
+        Ok(outcome)
+    }
+
+    /// Counts the tiles a dig revealed towards the player who made it.
+    fn credit(&mut self, user_id: u64, outcome: &MoveOutcome) {
+        if let Some(player) = self.players.iter_mut().find(|p| p.user_id == user_id) {
+            player.tiles_revealed += outcome
+                .revealed
+                .iter()
+                .filter(|position| {
+                    !self.game.tiles[position.1 as usize][position.0 as usize].is_mine
+                })
+                .count() as u32;
+        }
+
+        if outcome.new_state == Some(GameState::Lost) {
+            self.mine_hit_by = Some(user_id);
+        }
+    }
+
+    pub fn flag(&mut self, user_id: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        let outcome = self.game.flag(position)?;
+
+        if outcome.flag_change == Some(FlagChange::Placed) {
+            self.flagged_by.insert(position, user_id);
+        }
+        Ok(outcome)
+    }
+
+    pub fn unflag(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        let outcome = self.game.unflag(position)?;
+
+        if outcome.flag_change == Some(FlagChange::Removed) {
+            self.flagged_by.remove(&position);
+        }
+        Ok(outcome)
+    }
+
+    /// Flags placed by the player that are still standing on a mine.
+    pub fn correct_flags(&self, user_id: u64) -> u32 {
+        self.flagged_by
+            .iter()
+            .filter(|(position, flagger)| {
+                let tile = &self.game.tiles[position.1 as usize][position.0 as usize];
+                **flagger == user_id && tile.is_flagged && tile.is_mine
+            })
+            .count() as u32
+    }
+}
+
+impl Board for CoopGame {
+    fn game(&self) -> Option<&Game> {
+        Some(&self.game)
+    }
+
+    fn game_mut(&mut self) -> Option<&mut Game> {
+        Some(&mut self.game)
+    }
+
+    fn state(&self) -> GameState {
+        self.game.state
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.game.width, self.game.height)
+    }
+
+    fn number_of_mines(&self) -> u32 {
+        self.game.number_of_mines
+    }
+
+    fn tile(&self, position: (u32, u32)) -> Option<TileView> {
+        classic_tile(&self.game, position)
+    }
+
+    fn neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        self.game.neighbours(position)
+    }
+
+    fn time_started(&self) -> DateTime<Utc> {
+        self.game.time_started
+    }
+
+    fn last_activity(&self) -> DateTime<Utc> {
+        self.game.last_activity()
+    }
+
+    fn letter_columns(&self) -> bool {
+        self.game.letter_columns
+    }
+
+    fn set_letter_columns(&mut self, letter_columns: bool) {
+        self.game.letter_columns = letter_columns;
+    }
+
+    fn board_message(&self) -> Option<(u64, u64)> {
+        self.game.board_message
+    }
+
+    fn set_board_message(&mut self, message: Option<(u64, u64)>) {
+        self.game.board_message = message;
+    }
+
+    fn dig(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        CoopGame::dig(self, player, position)
+    }
+
+    fn flag(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        CoopGame::flag(self, player, position)
+    }
+
+    fn unflag(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        CoopGame::unflag(self, position)
+    }
+
+    fn chord(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        CoopGame::chord(self, player, position)
+    }
+
+    /// Everyone shares the board, so taking back a move could undo someone else's.
+    fn undo(&mut self, _player: u64) -> Result<(), MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn pause(&mut self, player: u64) -> Result<(), MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        self.game.pause()
+    }
+
+    fn resume(&mut self, player: u64) -> Result<(), MoveError> {
+        if !self.is_player(player) {
+            return Err(MoveError::NotAPlayer);
+        }
+        self.game.resume()
+    }
+
+    fn join(&mut self, player: u64, name: &str) -> Option<bool> {
+        Some(CoopGame::join(self, player, name))
+    }
+
+    fn can_stop(&self, player: u64) -> bool {
+        self.owner == player
+    }
+
+    fn introduction(&self) -> Option<String> {
+        Some(
+            "Co-op game started! Anyone in this channel can use the command joingame to help out."
+                .to_string(),
+        )
+    }
+
+    fn render(&self) -> Pixmap {
+        DefaultMinesweeperDrawer::draw_board(&self.game)
+    }
+
+    fn render_for_spectators(&self) -> Pixmap {
+        DefaultMinesweeperDrawer::draw_board(&self.game.spectator_view())
+    }
+
+    fn summary(&self) -> Summary {
+        let mut summary = Summary::classic(&self.game);
+
+        for player in &self.players {
+            summary.fields.push((
+                player.name.clone(),
+                format!(
+                    "{} tiles revealed\n{} correct flags",
+                    player.tiles_revealed,
+                    self.correct_flags(player.user_id)
+                ),
+                false,
+            ));
+        }
+
+        if let Some(player) = self
+            .mine_hit_by
+            .and_then(|id| self.players.iter().find(|p| p.user_id == id))
+        {
+            summary
+                .fields
+                .push(("Mine Hit By".to_string(), player.name.clone(), false));
+        }
+
+        summary
+    }
+}
@@ -0,0 +1,194 @@
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::prelude::TypeMapKey;
+
+use crate::events::{GameEvent, GameEventKind, GameListener};
+use crate::game::Game;
+use crate::store;
+
+pub const DAILY_DIFFICULTY: &str = "medium";
+/// Daily games are named games, so they can run next to a player's other games.
+pub const DAILY_GAME_NAME: &str = "daily";
+
+const DAILY_RESULTS_FILE: &str = "daily.json";
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum DailyOutcome {
+    InProgress,
+    Won { milliseconds: i64 },
+    Lost,
+    Forfeited,
+    Abandoned,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DailyEntry {
+    pub user_id: u64,
+    pub user_name: String,
+    pub outcome: DailyOutcome,
+    /// Whether the clock was stopped at any point during the attempt.
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Every daily attempt, keyed by the UTC date of the board it was played on.
+#[derive(Default)]
+pub struct DailyResults {
+    days: Mutex<HashMap<NaiveDate, Vec<DailyEntry>>>,
+}
+
+pub fn today() -> NaiveDate {
+    Utc::today().naive_utc()
+}
+
+pub fn daily_seed(date: NaiveDate) -> u64 {
+    date.num_days_from_ce() as u64
+}
+
+impl DailyResults {
+    /// Records the start of a user's ranked attempt. Returns false if they already used it.
+    pub fn start_attempt(&self, date: NaiveDate, user_id: u64, user_name: &str) -> bool {
+        let mut days = self.days.lock().unwrap();
+        let entries = days.entry(date).or_default();
+
+        if entries.iter().any(|e| e.user_id == user_id) {
+            return false;
+        }
+
+        entries.push(DailyEntry {
+            user_id,
+            user_name: user_name.to_string(),
+            outcome: DailyOutcome::InProgress,
+            paused: false,
+        });
+        true
+    }
+
+    fn attempt_in_progress(
+        days: &mut HashMap<NaiveDate, Vec<DailyEntry>>,
+        date: NaiveDate,
+        user_id: u64,
+    ) -> Option<&mut DailyEntry> {
+        days.get_mut(&date)?
+            .iter_mut()
+            .find(|e| e.user_id == user_id && e.outcome == DailyOutcome::InProgress)
+    }
+
+    /// Marks the user's attempt on the date's board as paused. Returns false if that attempt
+    /// isn't in progress.
+    pub fn mark_paused(&self, date: NaiveDate, user_id: u64) -> bool {
+        let mut days = self.days.lock().unwrap();
+
+        match Self::attempt_in_progress(&mut days, date, user_id) {
+            Some(entry) => {
+                entry.paused = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Finishes the user's attempt on the date's board. Returns false if that attempt isn't in
+    /// progress.
+    pub fn finish_attempt(&self, date: NaiveDate, user_id: u64, outcome: DailyOutcome) -> bool {
+        let mut days = self.days.lock().unwrap();
+
+        match Self::attempt_in_progress(&mut days, date, user_id) {
+            Some(entry) => {
+                entry.outcome = outcome;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes every attempt a user made, returning how many there were.
+    pub fn purge_user(&self, user_id: u64) -> usize {
+        let mut days = self.days.lock().unwrap();
+        let mut removed = 0;
+
+        for entries in days.values_mut() {
+            let count = entries.len();
+            entries.retain(|e| e.user_id != user_id);
+            removed += count - entries.len();
+        }
+        days.retain(|_, entries| !entries.is_empty());
+
+        removed
+    }
+
+    pub fn entries(&self, date: NaiveDate) -> Vec<DailyEntry> {
+        self.days
+            .lock()
+            .unwrap()
+            .get(&date)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Winning entries for the date, fastest first.
+    pub fn leaderboard(&self, date: NaiveDate) -> Vec<(DailyEntry, i64)> {
+        let mut winners = self
+            .entries(date)
+            .into_iter()
+            .filter_map(|entry| match entry.outcome {
+                DailyOutcome::Won { milliseconds } => Some((entry, milliseconds)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        winners.sort_by_key(|(_, milliseconds)| *milliseconds);
+        winners
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        store::save(DAILY_RESULTS_FILE, &*self.days.lock().unwrap())
+    }
+}
+
+pub fn load_daily_results() -> DailyResults {
+    DailyResults {
+        days: Mutex::new(store::load(DAILY_RESULTS_FILE)),
+    }
+}
+
+/// Records the result of a player's daily attempt as soon as their game ends.
+pub struct DailyAttemptListener {
+    pub results: Arc<DailyResults>,
+    pub user_id: u64,
+}
+
+impl GameListener for DailyAttemptListener {
+    fn on_event(&self, game: &Game, event: &GameEvent) {
+        let date = match game.daily_date {
+            Some(date) => date,
+            None => return,
+        };
+
+        let outcome = match event.kind {
+            GameEventKind::Paused => {
+                if self.results.mark_paused(date, self.user_id) {
+                    self.results.save().ok();
+                }
+                return;
+            }
+            GameEventKind::Won => DailyOutcome::Won {
+                milliseconds: game.active_time_until(event.time).num_milliseconds(),
+            },
+            GameEventKind::Lost => DailyOutcome::Lost,
+            _ => return,
+        };
+
+        if self.results.finish_attempt(date, self.user_id, outcome) {
+            self.results.save().ok();
+        }
+    }
+}
+
+pub struct DailyDataKey;
+
+impl TypeMapKey for DailyDataKey {
+    type Value = Arc<DailyResults>;
+}
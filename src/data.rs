@@ -1,28 +1,333 @@
-use std::{fs::File, sync::Arc};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
 
+use log::LevelFilter;
 use serde::{Deserialize, Serialize};
-use std::io::BufReader;
+
+use crate::guilds::{Limits, Preset, PresetError};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct AppConfiguration {
+    /// Can be left out when MINESWEEPER_TOKEN is set.
+    #[serde(default)]
     pub token: String,
+    /// Needed to register slash commands. Without it, only the ~ commands are available.
+    #[serde(default)]
+    pub application_id: Option<u64>,
+    /// Minutes without a move before the player is warned that their game will be closed.
+    #[serde(default = "default_idle_warning_minutes")]
+    pub idle_warning_minutes: i64,
+    /// Minutes without a move before the game is closed as abandoned.
+    #[serde(default = "default_idle_close_minutes")]
+    pub idle_close_minutes: i64,
+    /// Where the images and fonts are. Relative paths start next to the executable.
+    #[serde(default = "default_asset_dir")]
+    pub asset_dir: PathBuf,
+    /// Where daily.json, settings.json and guilds.json are kept. Relative paths start next to the
+    /// executable.
+    #[serde(default = "default_storage_dir")]
+    pub storage_dir: PathBuf,
+    /// Limits for every server, which a server's own limits can only tighten.
+    #[serde(default)]
+    pub limits: Limits,
+    /// Presets every server has, unless it saves its own under the same name.
+    #[serde(default)]
+    pub default_presets: BTreeMap<String, Preset>,
+    /// How many pixels wide each tile is in the images sent to Discord.
+    #[serde(default = "default_tile_size")]
+    pub tile_size: u32,
+    /// One of off, error, warn, info, debug or trace.
+    #[serde(default = "default_log_level")]
+    pub log_level: String,
+}
+
+fn default_idle_warning_minutes() -> i64 {
+    30
+}
+
+fn default_idle_close_minutes() -> i64 {
+    60
+}
+
+fn default_asset_dir() -> PathBuf {
+    PathBuf::from("assets")
+}
+
+fn default_storage_dir() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_tile_size() -> u32 {
+    MAX_TILE_SIZE
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+/// The size boards are drawn at. Smaller tile sizes scale the drawing down.
+pub const MAX_TILE_SIZE: u32 = 100;
+const MIN_TILE_SIZE: u32 = 20;
+
+impl AppConfiguration {
+    pub fn log_level(&self) -> LevelFilter {
+        LevelFilter::from_str(&self.log_level).unwrap_or(LevelFilter::Info)
+    }
+
+    fn apply_environment(&mut self) -> Result<(), ConfigError> {
+        if let Some(token) = env_var("MINESWEEPER_TOKEN") {
+            self.token = token;
+        }
+        if let Some(application_id) = env_var("MINESWEEPER_APPLICATION_ID") {
+            self.application_id = Some(parse_env("MINESWEEPER_APPLICATION_ID", &application_id)?);
+        }
+        if let Some(asset_dir) = env_var("MINESWEEPER_ASSET_DIR") {
+            self.asset_dir = PathBuf::from(asset_dir);
+        }
+        if let Some(storage_dir) = env_var("MINESWEEPER_STORAGE_DIR") {
+            self.storage_dir = PathBuf::from(storage_dir);
+        }
+        if let Some(tile_size) = env_var("MINESWEEPER_TILE_SIZE") {
+            self.tile_size = parse_env("MINESWEEPER_TILE_SIZE", &tile_size)?;
+        }
+        if let Some(log_level) = env_var("MINESWEEPER_LOG_LEVEL") {
+            self.log_level = log_level;
+        }
+        Ok(())
+    }
+
+    fn resolve_paths(&mut self, base: &Path) {
+        self.asset_dir = base.join(&self.asset_dir);
+        self.storage_dir = base.join(&self.storage_dir);
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        if self.token.trim().is_empty() {
+            return Err(ConfigError::MissingToken);
+        }
+        if self.idle_warning_minutes <= 0 || self.idle_close_minutes <= self.idle_warning_minutes {
+            return Err(ConfigError::IdleMinutes);
+        }
+        for directory in ["images", "fonts"] {
+            if !self.asset_dir.join(directory).is_dir() {
+                return Err(ConfigError::MissingAssets(self.asset_dir.join(directory)));
+            }
+        }
+        if !self.storage_dir.is_dir() {
+            return Err(ConfigError::MissingStorage(self.storage_dir.clone()));
+        }
+        if [
+            self.limits.max_width,
+            self.limits.max_height,
+            self.limits.max_mines,
+        ]
+        .contains(&Some(0))
+        {
+            return Err(ConfigError::ZeroLimit);
+        }
+        for (name, preset) in &self.default_presets {
+            preset.check(name).map_err(|e| ConfigError::Preset {
+                name: name.clone(),
+                error: e,
+            })?;
+        }
+        if !(MIN_TILE_SIZE..=MAX_TILE_SIZE).contains(&self.tile_size) {
+            return Err(ConfigError::TileSize(self.tile_size));
+        }
+        if LevelFilter::from_str(&self.log_level).is_err() {
+            return Err(ConfigError::LogLevel(self.log_level.clone()));
+        }
+        Ok(())
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
 }
 
-pub struct ConfigKey;
+fn parse_env<T: FromStr>(name: &'static str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::Environment {
+        name,
+        value: value.to_string(),
+    })
+}
+
+/// Why the bot can't start with the configuration it was given.
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(std::io::Error),
+    Parse(serde_json::Error),
+    Environment { name: &'static str, value: String },
+    MissingToken,
+    IdleMinutes,
+    MissingAssets(PathBuf),
+    MissingStorage(PathBuf),
+    ZeroLimit,
+    Preset { name: String, error: PresetError },
+    TileSize(u32),
+    LogLevel(String),
+    NotLoaded,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(e) => write!(f, "couldn't read config.json: {}", e),
+            ConfigError::Parse(e) => write!(f, "couldn't parse config.json: {}", e),
+            ConfigError::Environment { name, value } => {
+                write!(f, "{} is set to {}, which isn't valid", name, value)
+            }
+            ConfigError::MissingToken => write!(
+                f,
+                "no bot token was given. Set token in config.json or MINESWEEPER_TOKEN"
+            ),
+            ConfigError::IdleMinutes => write!(
+                f,
+                "idle_warning_minutes must be above 0 and below idle_close_minutes"
+            ),
+            ConfigError::MissingAssets(path) => {
+                write!(f, "the asset directory {} doesn't exist", path.display())
+            }
+            ConfigError::MissingStorage(path) => {
+                write!(f, "the storage directory {} doesn't exist", path.display())
+            }
+            ConfigError::ZeroLimit => write!(f, "limits must be above 0, or left out"),
+            ConfigError::Preset { name, error } => {
+                write!(f, "the default preset {} is invalid: {}", name, error)
+            }
+            ConfigError::TileSize(size) => write!(
+                f,
+                "tile_size is {}, but must be between {} and {}",
+                size, MIN_TILE_SIZE, MAX_TILE_SIZE
+            ),
+            ConfigError::LogLevel(level) => write!(
+                f,
+                "log_level is {}, but must be off, error, warn, info, debug or trace",
+                level
+            ),
+            ConfigError::NotLoaded => write!(f, "the configuration hasn't been loaded yet"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+static CONFIGURATION: OnceLock<AppConfiguration> = OnceLock::new();
+
+/// The configuration the bot started with. Everything reads it from here, including code that
+/// runs without a Context, such as loading assets.
+pub fn configuration() -> Result<&'static AppConfiguration, ConfigError> {
+    CONFIGURATION.get().ok_or(ConfigError::NotLoaded)
+}
+
+pub fn storage_path(file_name: &str) -> Result<PathBuf, ConfigError> {
+    Ok(configuration()?.storage_dir.join(file_name))
+}
 
-impl serenity::prelude::TypeMapKey for ConfigKey {
-    type Value = Arc<AppConfiguration>;
+/// The configured asset directory, or the default one next to the executable if the
+/// configuration hasn't been loaded.
+pub fn asset_dir() -> PathBuf {
+    match configuration() {
+        Ok(config) => config.asset_dir.clone(),
+        Err(_) => std::env::current_exe()
+            .unwrap_or_default()
+            .with_file_name(default_asset_dir()),
+    }
 }
 
-pub fn load_configuration() -> Result<AppConfiguration, Box<dyn std::error::Error>> {
-    let config_path = std::env::current_exe()?.with_file_name("config.json");
+/// Reads config.json next to the executable, applies the environment overrides and checks the
+/// result. config.json can be left out when the environment provides everything needed.
+pub fn load_configuration() -> Result<&'static AppConfiguration, ConfigError> {
+    let exe_dir = std::env::current_exe()
+        .map_err(ConfigError::Read)?
+        .with_file_name("");
+    let config_path = exe_dir.join("config.json");
+
+    let mut config = match File::open(config_path) {
+        Ok(file) => {
+            let mut deserializer = serde_json::Deserializer::from_reader(BufReader::new(file));
+            AppConfiguration::deserialize(&mut deserializer).map_err(ConfigError::Parse)?
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            serde_json::from_str("{}").map_err(ConfigError::Parse)?
+        }
+        Err(e) => return Err(ConfigError::Read(e)),
+    };
+
+    config.apply_environment()?;
+    config.resolve_paths(&exe_dir);
+    config.validate()?;
+
+    Ok(CONFIGURATION.get_or_init(|| config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A configuration that passes validation, using the assets in the repository.
+    fn valid_configuration() -> AppConfiguration {
+        let mut config: AppConfiguration = serde_json::from_str(r#"{"token": "token"}"#).unwrap();
+        config.resolve_paths(Path::new(env!("CARGO_MANIFEST_DIR")));
+        config
+    }
+
+    #[test]
+    fn defaults_with_a_token_are_valid() {
+        assert!(valid_configuration().validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_settings_are_reported() {
+        let mut config = valid_configuration();
+        config.token = " ".to_string();
+        assert!(matches!(config.validate(), Err(ConfigError::MissingToken)));
+
+        let mut config = valid_configuration();
+        config.idle_close_minutes = config.idle_warning_minutes;
+        assert!(matches!(config.validate(), Err(ConfigError::IdleMinutes)));
+
+        let mut config = valid_configuration();
+        config.asset_dir = config.storage_dir.join("missing");
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::MissingAssets(_))
+        ));
 
-    let file = File::open(config_path)?;
-    let reader = BufReader::new(file);
+        let mut config = valid_configuration();
+        config.limits.max_mines = Some(0);
+        assert!(matches!(config.validate(), Err(ConfigError::ZeroLimit)));
 
-    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let mut config = valid_configuration();
+        config.default_presets.insert(
+            "classic".to_string(),
+            Preset {
+                width: 8,
+                height: 8,
+                mines: 10,
+            },
+        );
+        assert!(matches!(
+            config.validate(),
+            Err(ConfigError::Preset {
+                error: PresetError::Name,
+                ..
+            })
+        ));
 
-    let config = AppConfiguration::deserialize(&mut deserializer)?;
+        let mut config = valid_configuration();
+        config.tile_size = MIN_TILE_SIZE - 1;
+        assert!(matches!(config.validate(), Err(ConfigError::TileSize(_))));
 
-    Ok(config)
+        let mut config = valid_configuration();
+        config.log_level = "loud".to_string();
+        assert!(matches!(config.validate(), Err(ConfigError::LogLevel(_))));
+    }
 }
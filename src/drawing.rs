@@ -1,7 +1,7 @@
-use std::fs::File;
-use std::io::Read;
-
+use crate::data;
+use crate::flags::FlagsGame;
 use crate::game::{Game, GameState};
+use crate::moves;
 use crate::text;
 
 use rand::Rng;
@@ -21,6 +21,9 @@ const NUMBER_COLORS: [(u8, u8, u8); 8] = [
     (128, 128, 128),
 ];
 
+const PLAYER_COLORS: [(u8, u8, u8); 2] = [(211, 47, 47), (25, 118, 210)];
+const CURRENT_TURN_COLOR: (u8, u8, u8) = (255, 235, 59);
+
 lazy_static! {
     static ref GRASS_COLOR_DARK: Color = Color::from_rgba8(162, 209, 73, 255);
     static ref GRASS_COLOR_LIGHT: Color = Color::from_rgba8(170, 215, 81, 255);
@@ -33,71 +36,53 @@ lazy_static! {
     static ref GRASS_OUTLINE_COLOR: Color = Color::from_rgba8(135, 175, 58, 255);
     static ref TOP_BAR_COLOR: Color = Color::from_rgba8(74, 117, 44, 255);
     static ref FLAG_PIXMAP: Pixmap = Pixmap::decode_png({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = data::asset_dir();
         path.push("images");
         path.push("Flag.png");
 
-        let file = File::open(path).unwrap();
-        &file.bytes().flatten().collect::<Vec<_>>()
+        &std::fs::read(path).unwrap()
     })
     .unwrap();
     static ref WARNING_PIXMAP: Pixmap = Pixmap::decode_png({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = data::asset_dir();
         path.push("images");
         path.push("Warning.png");
 
-        let file = File::open(path).unwrap();
-        &file.bytes().flatten().collect::<Vec<_>>()
+        &std::fs::read(path).unwrap()
     })
     .unwrap();
     static ref X_MARK_PIXMAP: Pixmap = Pixmap::decode_png({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = data::asset_dir();
         path.push("images");
         path.push("XMark.png");
 
-        let file = File::open(path).unwrap();
-        &file.bytes().flatten().collect::<Vec<_>>()
+        &std::fs::read(path).unwrap()
     })
     .unwrap();
     static ref EXPLOSION_PIXMAP: Pixmap = Pixmap::decode_png({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = data::asset_dir();
         path.push("images");
         path.push("Explosion.png");
 
-        let file = File::open(path).unwrap();
-        &file.bytes().flatten().collect::<Vec<_>>()
+        &std::fs::read(path).unwrap()
     })
     .unwrap();
     static ref CLOCK_PIXMAP: Pixmap = Pixmap::decode_png({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = data::asset_dir();
         path.push("images");
         path.push("Clock.png");
 
-        let file = File::open(path).unwrap();
-        &file.bytes().flatten().collect::<Vec<_>>()
+        &std::fs::read(path).unwrap()
     })
     .unwrap();
     static ref FLOWER_PIXMAPS: Vec<Pixmap> = (1..=14)
         .map(|num| {
-            let mut path = std::env::current_exe().unwrap();
-            path.pop();
-            path.push("assets");
+            let mut path = data::asset_dir();
             path.push("images");
             path.push("flowers");
             path.push(format!("Flower{}.png", num));
 
-            let file = File::open(path).unwrap();
-            Pixmap::decode_png(&file.bytes().flatten().collect::<Vec<_>>()).unwrap()
+            Pixmap::decode_png(&std::fs::read(path).unwrap()).unwrap()
         })
         .collect::<Vec<_>>();
 }
@@ -110,6 +95,38 @@ enum LineType {
 
 pub trait MinesweeperDrawer {
     fn draw_board(game: &Game) -> Pixmap;
+    fn draw_flags_board(flags_game: &FlagsGame) -> Pixmap;
+}
+
+/// Shrinks a drawn board to the configured tile size before it's sent.
+pub fn scale_to_tile_size(map: Pixmap) -> Pixmap {
+    let tile_size = data::configuration().map_or(data::MAX_TILE_SIZE, |config| config.tile_size);
+
+    if tile_size == data::MAX_TILE_SIZE {
+        return map;
+    }
+
+    let scale = tile_size as f32 / data::MAX_TILE_SIZE as f32;
+    let mut scaled = Pixmap::new(
+        ((map.width() as f32 * scale).round() as u32).max(1),
+        ((map.height() as f32 * scale).round() as u32).max(1),
+    )
+    .unwrap();
+
+    scaled.draw_pixmap(
+        0,
+        0,
+        map.as_ref(),
+        &PixmapPaint {
+            opacity: 1.0,
+            blend_mode: BlendMode::SourceOver,
+            quality: FilterQuality::Bicubic,
+        },
+        Transform::from_scale(scale, scale),
+        None,
+    );
+
+    scaled
 }
 
 pub struct DefaultMinesweeperDrawer;
@@ -191,8 +208,8 @@ impl DefaultMinesweeperDrawer {
         );
     }
 
-    fn add_border_number(position: (i32, i32), number: u32, map: &mut Pixmap) {
-        let text_map = text::text_to_pixmap(&number.to_string(), &*text::ROBOTO, 85.0, (0, 0, 0));
+    fn add_border_label(position: (i32, i32), label: &str, map: &mut Pixmap) {
+        let text_map = text::text_to_pixmap(label, &text::ROBOTO, 85.0, (0, 0, 0));
 
         map.draw_pixmap(
             position.0,
@@ -224,8 +241,13 @@ impl DefaultMinesweeperDrawer {
             map.fill_rect(rect, &paint, Transform::identity(), None);
 
             if x > 0 {
-                let x_offset = if x > 9 { -4 } else { 18 };
-                Self::add_border_number((x as i32 * 100 + x_offset, 7), x, map);
+                let label = if game.letter_columns {
+                    moves::column_name(x)
+                } else {
+                    x.to_string()
+                };
+                let x_offset = if label.len() > 1 { -4 } else { 18 };
+                Self::add_border_label((x as i32 * 100 + x_offset, 7), &label, map);
             }
         }
         for y in 1..=game.height {
@@ -242,7 +264,7 @@ impl DefaultMinesweeperDrawer {
             map.fill_rect(rect, &paint, Transform::identity(), None);
 
             let x_offset = if y > 9 { -4 } else { 18 };
-            Self::add_border_number((x_offset, y as i32 * 100 + 7), y, map);
+            Self::add_border_label((x_offset, y as i32 * 100 + 7), &y.to_string(), map);
         }
     }
 
@@ -345,7 +367,7 @@ impl DefaultMinesweeperDrawer {
     fn add_mine_count(position: (i32, i32), number: u32, map: &mut Pixmap) {
         let text_map = text::text_to_pixmap(
             &number.to_string(),
-            &*text::EB_GARAMOND,
+            &text::EB_GARAMOND,
             110.0,
             NUMBER_COLORS[number as usize],
         );
@@ -406,7 +428,7 @@ impl DefaultMinesweeperDrawer {
 
         let flag_count = text::text_to_pixmap(
             &(game.number_of_mines - game.placed_flag_count).to_string(),
-            &*text::ROBOTO,
+            &text::ROBOTO,
             80.0,
             (255, 255, 255),
         );
@@ -437,7 +459,7 @@ impl DefaultMinesweeperDrawer {
             ),
         );
 
-        let difference = game.last_move_time - game.time_started;
+        let difference = game.active_time();
 
         let difference_text = format!(
             "{:02}:{:02}",
@@ -446,7 +468,7 @@ impl DefaultMinesweeperDrawer {
         );
 
         let difference_pixmap =
-            text::text_to_pixmap(&difference_text, &*text::ROBOTO, 80.0, (255, 255, 255));
+            text::text_to_pixmap(&difference_text, &text::ROBOTO, 80.0, (255, 255, 255));
 
         Self::draw_icon_scaled(
             (
@@ -529,33 +551,18 @@ impl DefaultMinesweeperDrawer {
         }
     }
 
-    fn scale_pixmap(old_pixmap: PixmapRef, scale: (f32, f32)) -> Pixmap {
-        let mut map = Pixmap::new(
-            (old_pixmap.width() as f32 * scale.0) as u32,
-            (old_pixmap.height() as f32 * scale.1) as u32,
-        )
-        .unwrap();
-
-        map.draw_pixmap(
-            0,
-            0,
-            old_pixmap,
-            &PixmapPaint {
-                opacity: 255.0,
-                blend_mode: BlendMode::SourceOver,
-                quality: FilterQuality::Bilinear,
-            },
-            Transform::from_scale(scale.0, scale.1),
-            None,
-        );
-        map
+    /// Every mine is flagged once the game is won, so the flags go on top of the flowers.
+    fn add_won_flags(map: &mut Pixmap, game: &Game) {
+        for (y, x_row) in game.tiles.iter().enumerate() {
+            for (x, tile) in x_row.iter().enumerate() {
+                if tile.is_flagged {
+                    Self::draw_icon((x as i32, y as i32), map, FLAG_PIXMAP.as_ref());
+                }
+            }
+        }
     }
-}
-
-impl MinesweeperDrawer for DefaultMinesweeperDrawer {
-    fn draw_board(game: &Game) -> Pixmap {
-        let mut map = Pixmap::new((game.width + 1) * 100, (game.height + 1) * 100).unwrap();
 
+    fn draw_tiles(map: &mut Pixmap, game: &Game) {
         for (y, x_row) in game.tiles.iter().enumerate() {
             for (x, tile) in x_row.iter().enumerate() {
                 let rect =
@@ -582,19 +589,11 @@ impl MinesweeperDrawer for DefaultMinesweeperDrawer {
                         map.fill_rect(rect, &paint, Transform::identity(), None);
 
                         if tile.adjacent_mines > 0 {
-                            Self::add_mine_count(
-                                (x as i32, y as i32),
-                                tile.adjacent_mines,
-                                &mut map,
-                            )
+                            Self::add_mine_count((x as i32, y as i32), tile.adjacent_mines, map)
                         }
 
                         if tile.is_mine {
-                            Self::draw_icon(
-                                (x as i32, y as i32),
-                                &mut map,
-                                EXPLOSION_PIXMAP.as_ref(),
-                            );
+                            Self::draw_icon((x as i32, y as i32), map, EXPLOSION_PIXMAP.as_ref());
                         }
                     }
                 } else {
@@ -609,38 +608,205 @@ impl MinesweeperDrawer for DefaultMinesweeperDrawer {
 
                     if tile.is_flagged {
                         if game.state == GameState::Playing {
-                            Self::draw_icon((x as i32, y as i32), &mut map, FLAG_PIXMAP.as_ref());
+                            Self::draw_icon((x as i32, y as i32), map, FLAG_PIXMAP.as_ref());
                         }
                         if game.state == GameState::Lost {
                             if tile.is_mine {
-                                Self::draw_icon(
-                                    (x as i32, y as i32),
-                                    &mut map,
-                                    FLAG_PIXMAP.as_ref(),
-                                );
+                                Self::draw_icon((x as i32, y as i32), map, FLAG_PIXMAP.as_ref());
                             } else {
-                                Self::draw_icon(
-                                    (x as i32, y as i32),
-                                    &mut map,
-                                    X_MARK_PIXMAP.as_ref(),
-                                );
+                                Self::draw_icon((x as i32, y as i32), map, X_MARK_PIXMAP.as_ref());
                             }
                         }
                     }
 
                     if game.state == GameState::Lost && tile.is_mine {
-                        Self::draw_icon((x as i32, y as i32), &mut map, WARNING_PIXMAP.as_ref());
+                        Self::draw_icon((x as i32, y as i32), map, WARNING_PIXMAP.as_ref());
                     }
                 }
             }
         }
+    }
+
+    /// Covers the whole board in grass so a paused game gives nothing away.
+    fn draw_paused_tiles(map: &mut Pixmap, game: &Game) {
+        for y in 0..game.height as usize {
+            for x in 0..game.width as usize {
+                let rect =
+                    Rect::from_xywh(((x + 1) * 100) as f32, ((y + 1) * 100) as f32, 100.0, 100.0)
+                        .unwrap();
+
+                let color = if (y + x) % 2 == 0 {
+                    *GRASS_COLOR_DARK
+                } else {
+                    *GRASS_COLOR_LIGHT
+                };
+                map.fill_rect(
+                    rect,
+                    &create_default_paint(color),
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+
+        let text = text::text_to_pixmap("Paused", &text::ROBOTO, 160.0, (255, 255, 255));
+        map.draw_pixmap(
+            ((game.width + 2) * 50) as i32 - text.width() as i32 / 2,
+            ((game.height + 2) * 50) as i32 - text.height() as i32 / 2,
+            text.as_ref(),
+            &PixmapPaint::default(),
+            Transform::identity(),
+            None,
+        );
+    }
+
+    fn draw_claimed_mines(map: &mut Pixmap, flags_game: &FlagsGame) {
+        for (position, player) in &flags_game.claimed_by {
+            let rect = Rect::from_xywh(
+                ((position.0 + 1) * 100) as f32,
+                ((position.1 + 1) * 100) as f32,
+                100.0,
+                100.0,
+            )
+            .unwrap();
+
+            let color = PLAYER_COLORS[*player];
+            let paint = create_default_paint(Color::from_rgba8(color.0, color.1, color.2, 255));
+            map.fill_rect(rect, &paint, Transform::identity(), None);
 
-        Self::outline_tiles(&mut map, &game);
+            Self::draw_icon(
+                (position.0 as i32, position.1 as i32),
+                map,
+                FLAG_PIXMAP.as_ref(),
+            );
+        }
+    }
+
+    fn add_scores_top_bar(game_map: PixmapRef, flags_game: &FlagsGame) -> Pixmap {
+        let y_offset = (game_map.height() as f32 * 0.2) as u32;
+
+        let mut map = Pixmap::new(game_map.width(), game_map.height() + y_offset).unwrap();
+
+        map.draw_pixmap(
+            0,
+            y_offset as i32,
+            game_map,
+            &PixmapPaint {
+                opacity: 255.0,
+                blend_mode: BlendMode::SourceOver,
+                quality: FilterQuality::Bilinear,
+            },
+            Transform::identity(),
+            None,
+        );
+
+        let rect = Rect::from_xywh(0.0, 0.0, map.width() as f32, y_offset as f32).unwrap();
+        map.fill_rect(
+            rect,
+            &create_default_paint(*TOP_BAR_COLOR),
+            Transform::identity(),
+            None,
+        );
+
+        let half_width = map.width() as f32 / 2.0;
+        let square_size = y_offset as f32 * 0.5;
+
+        for (player, name) in flags_game.names.iter().enumerate() {
+            let x = half_width * player as f32 + square_size / 2.0;
+
+            let color = PLAYER_COLORS[player];
+            let rect = Rect::from_xywh(x, square_size / 2.0, square_size, square_size).unwrap();
+            map.fill_rect(
+                rect,
+                &create_default_paint(Color::from_rgba8(color.0, color.1, color.2, 255)),
+                Transform::identity(),
+                None,
+            );
+
+            let text_color = if player == flags_game.turn && !flags_game.is_finished() {
+                CURRENT_TURN_COLOR
+            } else {
+                (255, 255, 255)
+            };
+
+            let score = text::text_to_pixmap(
+                &format!(
+                    "{}: {}",
+                    drawable_name(name, player),
+                    flags_game.scores[player]
+                ),
+                &text::ROBOTO,
+                80.0,
+                text_color,
+            );
+
+            let scale = square_size / score.height() as f32 * 1.2;
+            let scale = scale.min((half_width - square_size * 2.0) / score.width() as f32);
+
+            Self::draw_icon_scaled(
+                (
+                    (x + square_size * 1.5) as i32,
+                    (square_size / 2.0 - square_size * 0.1) as i32,
+                ),
+                &mut map,
+                score.as_ref(),
+                (scale, scale),
+            );
+        }
+
+        Self::draw_line(
+            &mut map,
+            (0.0, y_offset as f32),
+            (100 + flags_game.game.width * 100) as f32,
+            &Color::BLACK,
+            LineType::Horizontal,
+        );
+        map
+    }
+
+    fn scale_pixmap(old_pixmap: PixmapRef, scale: (f32, f32)) -> Pixmap {
+        let mut map = Pixmap::new(
+            (old_pixmap.width() as f32 * scale.0) as u32,
+            (old_pixmap.height() as f32 * scale.1) as u32,
+        )
+        .unwrap();
+
+        map.draw_pixmap(
+            0,
+            0,
+            old_pixmap,
+            &PixmapPaint {
+                opacity: 255.0,
+                blend_mode: BlendMode::SourceOver,
+                quality: FilterQuality::Bilinear,
+            },
+            Transform::from_scale(scale.0, scale.1),
+            None,
+        );
+        map
+    }
+}
+
+impl MinesweeperDrawer for DefaultMinesweeperDrawer {
+    fn draw_board(game: &Game) -> Pixmap {
+        let mut map = Pixmap::new((game.width + 1) * 100, (game.height + 1) * 100).unwrap();
+
+        if game.paused {
+            Self::draw_paused_tiles(&mut map, game);
+            Self::add_border(&mut map, game);
+            Self::add_border_line(&mut map, game);
+            return Self::add_top_bar(map.as_ref(), game);
+        }
+
+        Self::draw_tiles(&mut map, game);
+
+        Self::outline_tiles(&mut map, game);
         if game.state == GameState::Won {
-            Self::add_flowers(&mut map, &game);
+            Self::add_flowers(&mut map, game);
+            Self::add_won_flags(&mut map, game);
         }
-        Self::add_border(&mut map, &game);
-        Self::add_border_line(&mut map, &game);
+        Self::add_border(&mut map, game);
+        Self::add_border_line(&mut map, game);
 
         if game.state == GameState::Lost {
             let rect = Rect::from_xywh(0.0, 0.0, map.width() as f32, map.height() as f32).unwrap();
@@ -657,7 +823,35 @@ impl MinesweeperDrawer for DefaultMinesweeperDrawer {
             );
         }
 
-        Self::add_top_bar(map.as_ref(), &game)
+        Self::add_top_bar(map.as_ref(), game)
+    }
+
+    fn draw_flags_board(flags_game: &FlagsGame) -> Pixmap {
+        let game = &flags_game.game;
+        let mut map = Pixmap::new((game.width + 1) * 100, (game.height + 1) * 100).unwrap();
+
+        Self::draw_tiles(&mut map, game);
+        Self::draw_claimed_mines(&mut map, flags_game);
+        Self::outline_tiles(&mut map, game);
+        Self::add_border(&mut map, game);
+        Self::add_border_line(&mut map, game);
+
+        Self::add_scores_top_bar(map.as_ref(), flags_game)
+    }
+}
+
+/// Player names can contain glyphs the bundled fonts don't have, so only plain ASCII is drawn.
+fn drawable_name(name: &str, player: usize) -> String {
+    let name = name
+        .chars()
+        .filter(|c| c.is_ascii_graphic() || *c == ' ')
+        .take(16)
+        .collect::<String>();
+
+    if name.trim().is_empty() {
+        format!("Player {}", player + 1)
+    } else {
+        name.trim().to_string()
     }
 }
 
@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+
+use crate::game::Game;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEventKind {
+    Started,
+    TileRevealed((u32, u32)),
+    FlagPlaced((u32, u32)),
+    FlagRemoved((u32, u32)),
+    Won,
+    Lost,
+    Paused,
+    Resumed,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GameEvent {
+    pub kind: GameEventKind,
+    pub time: DateTime<Utc>,
+}
+
+/// Something that wants to know what happens in a game, such as stats tracking or a replay
+/// log. Listeners are called after the move has been applied, so the game they're given
+/// already reflects the event.
+pub trait GameListener: Send + Sync {
+    fn on_event(&self, game: &Game, event: &GameEvent);
+}
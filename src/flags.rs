@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use tiny_skia::Pixmap;
+
+use crate::board::{classic_tile, Board, Summary, TileView};
+use crate::drawing::{DefaultMinesweeperDrawer, MinesweeperDrawer};
+use crate::game::{Game, GameState, GenerationError, MoveError, MoveOutcome};
+
+/// A two player game where players take turns digging and score a point for every mine they
+/// find. Finding a mine grants another turn, and the first player past half of the mines wins.
+pub struct FlagsGame {
+    pub game: Game,
+    pub players: [u64; 2],
+    pub names: [String; 2],
+    pub scores: [u32; 2],
+    pub turn: usize,
+    pub claimed_by: HashMap<(u32, u32), usize>,
+}
+
+impl FlagsGame {
+    pub fn new(
+        game_settings: (u32, u32, u32),
+        players: [u64; 2],
+        names: [String; 2],
+    ) -> Result<Self, GenerationError> {
+        let mut game = Game::new_generated(game_settings.0, game_settings.1, game_settings.2)?;
+        game.state = GameState::Playing;
+
+        Ok(FlagsGame {
+            game,
+            players,
+            names,
+            scores: [0, 0],
+            turn: 0,
+            claimed_by: HashMap::new(),
+        })
+    }
+
+    pub fn player_index(&self, user_id: u64) -> Option<usize> {
+        self.players.iter().position(|id| *id == user_id)
+    }
+
+    pub fn current_player(&self) -> u64 {
+        self.players[self.turn]
+    }
+
+    /// Digs for the current player. A claimed mine is the only tile revealed when one is found.
+    pub fn dig(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if self.is_finished() {
+            return Err(MoveError::GameOver);
+        }
+
+        if position.0 >= self.game.width || position.1 >= self.game.height {
+            return Err(MoveError::OutOfBounds);
+        }
+
+        let tile = &mut self.game.tiles[position.1 as usize][position.0 as usize];
+
+        if tile.is_revealed {
+            return Err(MoveError::AlreadyRevealed);
+        }
+
+        self.game.move_count += 1;
+
+        if tile.is_mine {
+            tile.is_revealed = true;
+            self.claimed_by.insert(position, self.turn);
+            self.scores[self.turn] += 1;
+            return Ok(MoveOutcome {
+                revealed: vec![position],
+                ..Default::default()
+            });
+        }
+
+        let revealed = self.game.reveal(position);
+        self.turn = 1 - self.turn;
+        Ok(MoveOutcome {
+            revealed,
+            ..Default::default()
+        })
+    }
+
+    /// The index of the player who is past half of the mines, if either is.
+    pub fn winner(&self) -> Option<usize> {
+        self.scores
+            .iter()
+            .position(|score| score * 2 > self.game.number_of_mines)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.winner().is_some() || self.scores[0] + self.scores[1] == self.game.number_of_mines
+    }
+}
+
+impl Board for FlagsGame {
+    fn state(&self) -> GameState {
+        self.game.state
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        (self.game.width, self.game.height)
+    }
+
+    fn number_of_mines(&self) -> u32 {
+        self.game.number_of_mines
+    }
+
+    fn tile(&self, position: (u32, u32)) -> Option<TileView> {
+        classic_tile(&self.game, position)
+    }
+
+    fn neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        self.game.neighbours(position)
+    }
+
+    fn time_started(&self) -> DateTime<Utc> {
+        self.game.time_started
+    }
+
+    fn last_activity(&self) -> DateTime<Utc> {
+        self.game.last_activity()
+    }
+
+    fn letter_columns(&self) -> bool {
+        self.game.letter_columns
+    }
+
+    fn set_letter_columns(&mut self, letter_columns: bool) {
+        self.game.letter_columns = letter_columns;
+    }
+
+    fn board_message(&self) -> Option<(u64, u64)> {
+        self.game.board_message
+    }
+
+    fn set_board_message(&mut self, message: Option<(u64, u64)>) {
+        self.game.board_message = message;
+    }
+
+    fn is_finished(&self) -> bool {
+        FlagsGame::is_finished(self)
+    }
+
+    fn dig(&mut self, player: u64, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        if self.player_index(player).is_none() {
+            return Err(MoveError::NotAPlayer);
+        }
+        if self.current_player() != player {
+            return Err(MoveError::NotYourTurn(self.names[self.turn].clone()));
+        }
+        FlagsGame::dig(self, position)
+    }
+
+    fn flag(&mut self, _player: u64, _position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn unflag(&mut self, _player: u64, _position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn chord(&mut self, _player: u64, _position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn undo(&mut self, _player: u64) -> Result<(), MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    /// There are no flags to trust, and every dig is a turn.
+    fn dig_satisfied_tiles(&mut self, _player: u64) -> Vec<(u32, u32)> {
+        vec![]
+    }
+
+    fn pause(&mut self, _player: u64) -> Result<(), MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn resume(&mut self, _player: u64) -> Result<(), MoveError> {
+        Err(MoveError::Unsupported)
+    }
+
+    fn can_stop(&self, player: u64) -> bool {
+        self.player_index(player).is_some()
+    }
+
+    fn introduction(&self) -> Option<String> {
+        Some(format!(
+            "Flagstorm between {} and {}! Take turns using dig to find mines. Finding a mine scores a point and gives you another turn, and the first to claim more than {} mines wins. {} goes first.",
+            self.names[0],
+            self.names[1],
+            self.game.number_of_mines / 2,
+            self.names[self.turn]
+        ))
+    }
+
+    fn announcement(&self, outcome: &MoveOutcome) -> Option<String> {
+        let claimed = outcome
+            .revealed
+            .iter()
+            .any(|position| self.claimed_by.contains_key(position));
+
+        if claimed {
+            Some(format!(
+                "{} found a mine and goes again!",
+                self.names[self.turn]
+            ))
+        } else {
+            None
+        }
+    }
+
+    fn render(&self) -> Pixmap {
+        DefaultMinesweeperDrawer::draw_flags_board(self)
+    }
+
+    /// Everything on a flagstorm board is already public.
+    fn render_for_spectators(&self) -> Pixmap {
+        self.render()
+    }
+
+    fn summary(&self) -> Summary {
+        let mut fields = self
+            .names
+            .iter()
+            .zip(self.scores)
+            .map(|(name, score)| (name.clone(), format!("{} mines", score), true))
+            .collect::<Vec<_>>();
+        fields.push((
+            "Mine Count".to_string(),
+            self.game.number_of_mines.to_string(),
+            true,
+        ));
+
+        Summary {
+            title: "Flagstorm Summary".to_string(),
+            description: match self.winner() {
+                Some(winner) => format!("{} wins!", self.names[winner]),
+                None => "It's a draw!".to_string(),
+            },
+            fields,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags_game(mines: u32) -> FlagsGame {
+        FlagsGame::new((6, 6, mines), [1, 2], ["Ann".to_string(), "Bo".to_string()]).unwrap()
+    }
+
+    fn tiles(game: &FlagsGame, is_mine: bool) -> Vec<(u32, u32)> {
+        (0..game.game.height)
+            .flat_map(|y| (0..game.game.width).map(move |x| (x, y)))
+            .filter(|(x, y)| game.game.tiles[*y as usize][*x as usize].is_mine == is_mine)
+            .collect()
+    }
+
+    #[test]
+    fn finding_a_mine_scores_and_keeps_the_turn() {
+        let mut game = flags_game(5);
+        let mine = tiles(&game, true)[0];
+
+        Board::dig(&mut game, 1, mine).unwrap();
+
+        assert_eq!(game.scores, [1, 0]);
+        assert_eq!(game.claimed_by.get(&mine), Some(&0));
+        assert_eq!(game.current_player(), 1);
+    }
+
+    #[test]
+    fn missing_passes_the_turn() {
+        let mut game = flags_game(5);
+        let safe = tiles(&game, false)[0];
+
+        Board::dig(&mut game, 1, safe).unwrap();
+
+        assert_eq!(game.scores, [0, 0]);
+        assert_eq!(game.current_player(), 2);
+        assert!(matches!(
+            Board::dig(&mut game, 1, safe),
+            Err(MoveError::NotYourTurn(_))
+        ));
+        assert!(matches!(
+            Board::dig(&mut game, 3, safe),
+            Err(MoveError::NotAPlayer)
+        ));
+    }
+
+    #[test]
+    fn the_first_player_past_half_of_the_mines_wins() {
+        let mut game = flags_game(5);
+        let mines = tiles(&game, true);
+
+        for mine in &mines[..2] {
+            Board::dig(&mut game, 1, *mine).unwrap();
+        }
+        assert_eq!(game.winner(), None);
+        assert!(!Board::is_finished(&game));
+
+        Board::dig(&mut game, 1, mines[2]).unwrap();
+        assert_eq!(game.winner(), Some(0));
+        assert!(Board::is_finished(&game));
+        assert!(matches!(
+            Board::dig(&mut game, 1, mines[3]),
+            Err(MoveError::GameOver)
+        ));
+    }
+
+    #[test]
+    fn splitting_the_mines_evenly_is_a_draw() {
+        let mut game = flags_game(2);
+        let mines = tiles(&game, true);
+
+        FlagsGame::dig(&mut game, mines[0]).unwrap();
+        game.turn = 1;
+        FlagsGame::dig(&mut game, mines[1]).unwrap();
+
+        assert_eq!(game.scores, [1, 1]);
+        assert_eq!(game.winner(), None);
+        assert!(game.is_finished());
+    }
+}
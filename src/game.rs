@@ -1,10 +1,10 @@
-use chrono::{DateTime, Utc};
-use dashmap::DashMap;
-use num_integer::Roots;
-use rand::Rng;
-use serenity::prelude::TypeMapKey;
-use std::{sync::Arc, time::SystemTime};
+use crate::events::{GameEvent, GameEventKind, GameListener};
+use crate::solver::{self, Rating, Tier};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{fmt, sync::Arc, time::SystemTime};
 
+#[derive(Clone, Default)]
 pub struct Tile {
     pub is_mine: bool,
     pub is_flagged: bool,
@@ -23,7 +23,7 @@ impl Tile {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum GameState {
     Won,
     Playing,
@@ -31,6 +31,184 @@ pub enum GameState {
     NotStarted,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum GenerationError {
+    NoMines,
+    TooManyMines { requested: u32, available: u32 },
+}
+
+impl fmt::Display for GenerationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerationError::NoMines => write!(f, "a board needs at least one mine"),
+            GenerationError::TooManyMines {
+                requested,
+                available,
+            } => write!(
+                f,
+                "{} mines were requested, but only {} tiles can hold one",
+                requested, available
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GenerationError {}
+
+/// Why a move was refused. Nothing about the game changes when one of these is returned.
+#[derive(Debug, PartialEq)]
+pub enum MoveError {
+    OutOfBounds,
+    NotStarted,
+    GameOver,
+    AlreadyRevealed,
+    AlreadyFlagged,
+    NotFlagged,
+    Flagged,
+    FlagLimitReached,
+    NotAPlayer,
+    NotYourTurn(String),
+    Unsupported,
+    Paused,
+    NotPaused,
+    NotPlaying,
+    NotANumber,
+    NotSatisfied,
+    NothingToUndo,
+    UndoDisabled,
+    Generation(GenerationError),
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::OutOfBounds => write!(f, "Coordinates out of bounds!"),
+            MoveError::NotStarted => write!(f, "Dig a tile before placing any flags!"),
+            MoveError::GameOver => write!(f, "This game is already over!"),
+            MoveError::AlreadyRevealed => write!(f, "That tile has already been dug!"),
+            MoveError::AlreadyFlagged => write!(f, "That tile is already flagged!"),
+            MoveError::NotFlagged => write!(f, "That tile isn't flagged!"),
+            MoveError::Flagged => write!(f, "That tile is flagged! Unflag it before digging it."),
+            MoveError::FlagLimitReached => {
+                write!(f, "You've already placed a flag for every mine!")
+            }
+            MoveError::NotAPlayer => write!(f, "You're not taking part in this game!"),
+            MoveError::NotYourTurn(player) => write!(f, "It's {}'s turn!", player),
+            MoveError::Unsupported => write!(f, "That move isn't part of this game mode!"),
+            MoveError::Paused => write!(
+                f,
+                "This game is paused! Use the command resume to keep playing."
+            ),
+            MoveError::NotPaused => write!(f, "This game isn't paused!"),
+            MoveError::NotPlaying => write!(f, "The clock only starts after the first dig!"),
+            MoveError::NotANumber => {
+                write!(f, "Only a dug tile with a number on it can be chorded!")
+            }
+            MoveError::NotSatisfied => write!(
+                f,
+                "That number doesn't have as many flags around it as it has mines!"
+            ),
+            MoveError::NothingToUndo => write!(f, "There's no move to take back!"),
+            MoveError::UndoDisabled => write!(f, "Moves can't be taken back in this game!"),
+            MoveError::Generation(e) => write!(f, "Couldn't generate the board: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+impl From<GenerationError> for MoveError {
+    fn from(e: GenerationError) -> Self {
+        MoveError::Generation(e)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FlagChange {
+    Placed,
+    Removed,
+}
+
+/// Everything a successful move changed.
+#[derive(Default, Debug)]
+pub struct MoveOutcome {
+    pub revealed: Vec<(u32, u32)>,
+    pub flag_change: Option<FlagChange>,
+    pub new_state: Option<GameState>,
+}
+
+/// The board as it was before the last move, so the move can be taken back.
+struct Snapshot {
+    tiles: Vec<Vec<Tile>>,
+    unmined_tiles: u32,
+    placed_flag_count: u32,
+}
+
+/// The mines for a game's first dig, generated before the dig is played.
+pub struct Layout {
+    tiles: Vec<Vec<Tile>>,
+    first_dig: (u32, u32),
+    rating: Rating,
+}
+
+/// Which tiles around the first dig are guaranteed to be free of mines.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SafetyPolicy {
+    Unprotected,
+    SafeTile,
+    Opening,
+    Radius(u32),
+}
+
+impl SafetyPolicy {
+    pub const MAX_RADIUS: u32 = 4;
+
+    pub fn is_protected(&self, position: (u32, u32), dug_position: (u32, u32)) -> bool {
+        let x_distance = (dug_position.0 as i32 - position.0 as i32).abs();
+        let y_distance = (dug_position.1 as i32 - position.1 as i32).abs();
+
+        match self {
+            SafetyPolicy::Unprotected => false,
+            SafetyPolicy::SafeTile => x_distance == 0 && y_distance == 0,
+            SafetyPolicy::Opening => x_distance <= 1 && y_distance <= 1,
+            SafetyPolicy::Radius(radius) => {
+                x_distance * x_distance + y_distance * y_distance < (radius * radius) as i32
+            }
+        }
+    }
+
+    /// Parses the names used by startgame: nosafety, safetile, opening and radius=N.
+    pub fn parse(policy: &str) -> Option<Self> {
+        match policy {
+            "nosafety" => Some(SafetyPolicy::Unprotected),
+            "safetile" => Some(SafetyPolicy::SafeTile),
+            "opening" => Some(SafetyPolicy::Opening),
+            _ => policy
+                .strip_prefix("radius=")
+                .and_then(|radius| radius.parse::<u32>().ok())
+                .filter(|radius| (1..=Self::MAX_RADIUS).contains(radius))
+                .map(SafetyPolicy::Radius),
+        }
+    }
+}
+
+impl Default for SafetyPolicy {
+    fn default() -> Self {
+        SafetyPolicy::Radius(3)
+    }
+}
+
+impl fmt::Display for SafetyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SafetyPolicy::Unprotected => write!(f, "No protection"),
+            SafetyPolicy::SafeTile => write!(f, "Safe first tile"),
+            SafetyPolicy::Opening => write!(f, "Guaranteed opening"),
+            SafetyPolicy::Radius(radius) => write!(f, "Safe radius of {}", radius),
+        }
+    }
+}
+
 pub struct Game {
     pub height: u32,
     pub width: u32,
@@ -41,9 +219,37 @@ pub struct Game {
     pub state: GameState,
     pub time_started: DateTime<Utc>,
     pub last_move_time: DateTime<Utc>,
+    pub move_count: u32,
+    pub safety_policy: SafetyPolicy,
+    pub paused: bool,
+    pub pause_count: u32,
+    /// When the clock was last started or resumed.
+    pub active_since: DateTime<Utc>,
+    /// Playing time from every interval before the last pause.
+    pub banked_time: Duration,
+    /// The board is generated until one of this tier is found, if a tier was asked for.
+    pub requested_tier: Option<Tier>,
+    /// How hard the board is, worked out when its mines were generated.
+    pub rating: Option<Rating>,
+    pub first_dig: Option<(u32, u32)>,
+    /// The channel the game was started in, where anything about it that isn't a reply goes.
+    pub channel_id: Option<u64>,
+    /// Whether the columns are labelled with letters instead of numbers when drawn.
+    pub letter_columns: bool,
+    /// The channel and id of the message the board was last shown in, which later moves edit.
+    pub board_message: Option<(u64, u64)>,
+    /// Whether undo is allowed. Ranked games turn it off.
+    pub allow_undo: bool,
+    /// The date of the daily challenge board this game is, if it is one.
+    pub daily_date: Option<NaiveDate>,
+    pending_layout: Option<Layout>,
+    undo_snapshot: Option<Snapshot>,
+    listeners: Vec<Arc<dyn GameListener>>,
 }
 
 impl Game {
+    const MAX_RATING_ATTEMPTS: u32 = 100;
+
     pub fn new(width: u32, height: u32, number_of_mines: u32) -> Self {
         Game {
             height,
@@ -57,20 +263,89 @@ impl Game {
             state: GameState::NotStarted,
             time_started: DateTime::<Utc>::from(SystemTime::now()),
             last_move_time: DateTime::<Utc>::from(SystemTime::now()),
+            move_count: 0,
+            safety_policy: SafetyPolicy::default(),
+            paused: false,
+            pause_count: 0,
+            active_since: DateTime::<Utc>::from(SystemTime::now()),
+            banked_time: Duration::zero(),
+            requested_tier: None,
+            rating: None,
+            first_dig: None,
+            channel_id: None,
+            letter_columns: false,
+            board_message: None,
+            allow_undo: true,
+            daily_date: None,
+            pending_layout: None,
+            undo_snapshot: None,
+            listeners: vec![],
+        }
+    }
+
+    /// Creates a game with every mine already placed, for modes that never dig on the server side.
+    pub fn new_generated(
+        width: u32,
+        height: u32,
+        number_of_mines: u32,
+    ) -> Result<Self, GenerationError> {
+        let mut game = Game::new(width, height, number_of_mines);
+        game.generate_mines(None, &mut rand::thread_rng())?;
+        Ok(game)
+    }
+
+    pub fn subscribe(&mut self, listener: Arc<dyn GameListener>) {
+        self.listeners.push(listener);
+    }
+
+    fn emit(&self, kind: GameEventKind) {
+        let event = GameEvent {
+            kind,
+            time: DateTime::<Utc>::from(SystemTime::now()),
+        };
+
+        for listener in &self.listeners {
+            listener.on_event(self, &event);
+        }
+    }
+
+    fn emit_outcome(&self, position: (u32, u32), outcome: &MoveOutcome) {
+        if outcome.new_state == Some(GameState::Playing) {
+            self.emit(GameEventKind::Started);
+        }
+
+        for revealed in &outcome.revealed {
+            self.emit(GameEventKind::TileRevealed(*revealed));
+        }
+
+        match outcome.flag_change {
+            Some(FlagChange::Placed) => self.emit(GameEventKind::FlagPlaced(position)),
+            Some(FlagChange::Removed) => self.emit(GameEventKind::FlagRemoved(position)),
+            None => (),
+        }
+
+        match outcome.new_state {
+            Some(GameState::Won) => self.emit(GameEventKind::Won),
+            Some(GameState::Lost) => self.emit(GameEventKind::Lost),
+            _ => (),
         }
     }
 
     fn is_out_of_bounds(&self, position: (i32, i32)) -> bool {
-        if position.0 < 0 || position.0 as i32 > self.width as i32 - 1 {
+        if position.0 < 0 || position.0 > self.width as i32 - 1 {
             return true;
         }
-        if position.1 < 0 || position.1 as i32 > self.height as i32 - 1 {
+        if position.1 < 0 || position.1 > self.height as i32 - 1 {
             return true;
         }
-        return false;
+        false
     }
 
-    fn make_adjacent_tiles_visible(&mut self, position: (i32, i32)) {
+    fn make_adjacent_tiles_visible(
+        &mut self,
+        position: (i32, i32),
+        revealed: &mut Vec<(u32, u32)>,
+    ) {
         if self.is_out_of_bounds((position.0, position.1)) {
             return;
         }
@@ -83,6 +358,7 @@ impl Game {
 
         tile.is_revealed = true;
         self.unmined_tiles -= 1;
+        revealed.push((position.0 as u32, position.1 as u32));
 
         if tile.is_flagged {
             tile.is_flagged = false;
@@ -93,49 +369,55 @@ impl Game {
             return;
         }
 
-        self.make_adjacent_tiles_visible((position.0 - 1, position.1));
-        self.make_adjacent_tiles_visible((position.0 + 1, position.1));
-        self.make_adjacent_tiles_visible((position.0 - 1, position.1 - 1));
-        self.make_adjacent_tiles_visible((position.0 - 1, position.1 + 1));
-        self.make_adjacent_tiles_visible((position.0 + 1, position.1 - 1));
-        self.make_adjacent_tiles_visible((position.0 + 1, position.1 + 1));
-        self.make_adjacent_tiles_visible((position.0, position.1 - 1));
-        self.make_adjacent_tiles_visible((position.0, position.1 + 1));
+        self.make_adjacent_tiles_visible((position.0 - 1, position.1), revealed);
+        self.make_adjacent_tiles_visible((position.0 + 1, position.1), revealed);
+        self.make_adjacent_tiles_visible((position.0 - 1, position.1 - 1), revealed);
+        self.make_adjacent_tiles_visible((position.0 - 1, position.1 + 1), revealed);
+        self.make_adjacent_tiles_visible((position.0 + 1, position.1 - 1), revealed);
+        self.make_adjacent_tiles_visible((position.0 + 1, position.1 + 1), revealed);
+        self.make_adjacent_tiles_visible((position.0, position.1 - 1), revealed);
+        self.make_adjacent_tiles_visible((position.0, position.1 + 1), revealed);
     }
 
-    fn can_place_mine(&self, position: (u32, u32), dug_position: (u32, u32)) -> bool {
-        if ((dug_position.0 as i32 - position.0 as i32)
-            * (dug_position.0 as i32 - position.0 as i32)
-            + (dug_position.1 as i32 - position.1 as i32)
-                * (dug_position.1 as i32 - position.1 as i32))
-            .sqrt()
-            < 3
-        {
-            return false;
-        }
+    /// Reveals a tile that isn't a mine, along with the opening around it if it has no adjacent
+    /// mines.
+    pub fn reveal(&mut self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        let mut revealed = vec![];
+        self.make_adjacent_tiles_visible((position.0 as i32, position.1 as i32), &mut revealed);
+        revealed
+    }
 
-        if self.tiles[position.1 as usize][position.0 as usize].is_mine {
-            false
-        } else {
-            true
+    fn can_place_mine(&self, position: (u32, u32), dug_position: Option<(u32, u32)>) -> bool {
+        if let Some(dug_position) = dug_position {
+            if self.safety_policy.is_protected(position, dug_position) {
+                return false;
+            }
         }
+
+        !self.tiles[position.1 as usize][position.0 as usize].is_mine
     }
 
-    fn generate_mines(&mut self, position: (u32, u32)) {
-        for _ in 0..self.number_of_mines {
-            let mut random_position = (
-                rand::thread_rng().gen_range(0..self.width),
-                rand::thread_rng().gen_range(0..self.height),
-            );
+    fn generate_mines(
+        &mut self,
+        position: Option<(u32, u32)>,
+        rng: &mut impl Rng,
+    ) -> Result<(), GenerationError> {
+        let mut candidates = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|candidate| self.can_place_mine(*candidate, position))
+            .collect::<Vec<_>>();
 
-            while !self.can_place_mine(random_position, position) {
-                random_position = (
-                    rand::thread_rng().gen_range(0..self.width),
-                    rand::thread_rng().gen_range(0..self.height),
-                );
-            }
+        if candidates.len() < self.number_of_mines as usize {
+            return Err(GenerationError::TooManyMines {
+                requested: self.number_of_mines,
+                available: candidates.len() as u32,
+            });
+        }
+
+        let (mine_positions, _) = candidates.partial_shuffle(rng, self.number_of_mines as usize);
 
-            self.tiles[random_position.1 as usize][random_position.0 as usize].is_mine = true;
+        for mine_position in mine_positions {
+            self.tiles[mine_position.1 as usize][mine_position.0 as usize].is_mine = true;
         }
 
         for y in 0..self.height as i32 {
@@ -172,80 +454,850 @@ impl Game {
                 self.tiles[y as usize][x as usize].adjacent_mines = adjacent_mines;
             }
         }
+
+        Ok(())
     }
 
-    fn start_dig(&mut self, position: (u32, u32)) {
+    /// Checks that the mines fit on the board wherever the first dig lands. The middle of the
+    /// board is the worst case, since the safe zone around it is never cut off by an edge.
+    pub fn validate(&self) -> Result<(), GenerationError> {
+        let center = (self.width / 2, self.height / 2);
+
+        let available = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|position| !self.safety_policy.is_protected(*position, center))
+            .count() as u32;
+
+        if self.number_of_mines == 0 {
+            return Err(GenerationError::NoMines);
+        }
+        if available < self.number_of_mines {
+            return Err(GenerationError::TooManyMines {
+                requested: self.number_of_mines,
+                available,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn start_dig(
+        &mut self,
+        position: (u32, u32),
+        rng: &mut impl Rng,
+    ) -> Result<MoveOutcome, MoveError> {
+        let layout = match self.pending_layout.take() {
+            Some(layout) if layout.first_dig == position => layout,
+            _ => self.generate_layout_with(position, rng)?,
+        };
+
+        self.tiles = layout.tiles;
+        self.rating = Some(layout.rating);
+        Ok(self.begin(position))
+    }
+
+    /// A game of the same size and settings that hasn't been started, to generate this game's
+    /// layout on without holding on to this game.
+    pub fn blank_copy(&self) -> Game {
+        let mut copy = Game::new(self.width, self.height, self.number_of_mines);
+        copy.safety_policy = self.safety_policy;
+        copy.requested_tier = self.requested_tier;
+        copy
+    }
+
+    /// Generates and rates the mines a first dig at `position` would get, without changing the
+    /// game. Rating plays the board through, as many as MAX_RATING_ATTEMPTS times when a tier was
+    /// asked for, so this is too slow to run on the async runtime.
+    pub fn generate_layout(&self, position: (u32, u32)) -> Result<Layout, GenerationError> {
+        self.generate_layout_with(position, &mut rand::thread_rng())
+    }
+
+    fn generate_layout_with(
+        &self,
+        position: (u32, u32),
+        rng: &mut impl Rng,
+    ) -> Result<Layout, GenerationError> {
+        let mut scratch = self.blank_copy();
+
+        let rating = match self.requested_tier {
+            Some(tier) => scratch.generate_rated_mines(position, tier, rng)?,
+            None => {
+                scratch.generate_mines(Some(position), rng)?;
+                solver::rate_layout(&scratch.tiles, scratch.number_of_mines, position)
+            }
+        };
+
+        Ok(Layout {
+            tiles: scratch.tiles,
+            first_dig: position,
+            rating,
+        })
+    }
+
+    /// Keeps a layout from [`Game::generate_layout`] for the first dig, which uses it if it lands
+    /// where the layout was generated for.
+    pub fn prepare_layout(&mut self, layout: Layout) {
+        if self.state == GameState::NotStarted {
+            self.pending_layout = Some(layout);
+        }
+    }
+
+    /// Generates boards until one is rated at the requested tier, settling for the closest one
+    /// if none of the attempts are. Returns the rating of the board it kept.
+    fn generate_rated_mines(
+        &mut self,
+        position: (u32, u32),
+        tier: Tier,
+        rng: &mut impl Rng,
+    ) -> Result<Rating, GenerationError> {
+        let mut closest: Option<(u32, Rating, Vec<Vec<Tile>>)> = None;
+
+        for _ in 0..Self::MAX_RATING_ATTEMPTS {
+            self.tiles = (0..self.height)
+                .map(|_| (0..self.width).map(|_| Tile::new()).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            self.generate_mines(Some(position), rng)?;
+
+            let rating = solver::rate_layout(&self.tiles, self.number_of_mines, position);
+            let distance = (rating.tier as i32 - tier as i32).unsigned_abs();
+
+            if distance == 0 {
+                return Ok(rating);
+            }
+
+            match &closest {
+                Some((closest_distance, _, _)) if *closest_distance <= distance => (),
+                _ => closest = Some((distance, rating, self.tiles.clone())),
+            }
+        }
+
+        let (_, rating, tiles) = closest.expect("at least one board was generated");
+        self.tiles = tiles;
+        Ok(rating)
+    }
+
+    fn begin(&mut self, position: (u32, u32)) -> MoveOutcome {
+        self.pending_layout = None;
         self.time_started = DateTime::<Utc>::from(SystemTime::now());
-        self.last_move_time = self.time_started.clone();
-        self.generate_mines(position);
-        self.make_adjacent_tiles_visible((position.0 as i32, position.1 as i32));
+        self.active_since = self.time_started;
+        self.first_dig = Some(position);
         self.state = GameState::Playing;
 
-        if self.unmined_tiles == self.number_of_mines {
-            self.state = GameState::Won;
+        let mut outcome = self.single_dig(position);
+        if outcome.new_state.is_none() {
+            outcome.new_state = Some(GameState::Playing);
         }
+        outcome
     }
 
-    fn single_dig(&mut self, position: (u32, u32)) {
+    fn single_dig(&mut self, position: (u32, u32)) -> MoveOutcome {
         self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
         let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
 
-        if tile.is_flagged {
-            return;
-        }
-
         if tile.is_mine {
             tile.is_revealed = true;
             self.state = GameState::Lost;
-            return;
+            return MoveOutcome {
+                revealed: vec![position],
+                new_state: Some(GameState::Lost),
+                ..Default::default()
+            };
         }
 
-        self.make_adjacent_tiles_visible((position.0 as i32, position.1 as i32));
+        let revealed = self.reveal(position);
 
-        if self.unmined_tiles == self.number_of_mines {
+        let new_state = if self.unmined_tiles == self.number_of_mines {
             self.state = GameState::Won;
+            self.flag_remaining_mines();
+            Some(GameState::Won)
+        } else {
+            None
+        };
+
+        MoveOutcome {
+            revealed,
+            new_state,
+            ..Default::default()
         }
     }
 
-    pub fn dig(&mut self, position: (u32, u32)) {
-        match &self.state {
-            GameState::NotStarted => self.start_dig(position),
-            GameState::Playing => self.single_dig(position),
-            _ => (),
+    /// Marks every mine once the game is won, since they're all known by then.
+    fn flag_remaining_mines(&mut self) {
+        for tile in self.tiles.iter_mut().flatten() {
+            tile.is_flagged = tile.is_mine;
         }
+        self.placed_flag_count = self.number_of_mines;
     }
 
-    pub fn flag(&mut self, position: (u32, u32)) {
-        if self.state != GameState::Playing {
-            return;
+    /// Unrevealed tiles next to a number that already has as many flags around it as it has
+    /// mines. These are only safe if the player's flags are right.
+    pub fn satisfied_tiles(&self) -> Vec<(u32, u32)> {
+        let mut satisfied = vec![];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let tile = &self.tiles[y as usize][x as usize];
+                if !tile.is_revealed || tile.is_mine || tile.adjacent_mines == 0 {
+                    continue;
+                }
+
+                if !self.is_satisfied((x, y)) {
+                    continue;
+                }
+
+                for neighbour in self.neighbours((x, y)) {
+                    let tile = &self.tiles[neighbour.1 as usize][neighbour.0 as usize];
+                    if !tile.is_revealed && !tile.is_flagged && !satisfied.contains(&neighbour) {
+                        satisfied.push(neighbour);
+                    }
+                }
+            }
         }
 
-        self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
-        let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
+        satisfied
+    }
+
+    /// Whether a tile has exactly as many flags around it as it has mines.
+    fn is_satisfied(&self, position: (u32, u32)) -> bool {
+        let flags = self
+            .neighbours(position)
+            .iter()
+            .filter(|(x, y)| self.tiles[*y as usize][*x as usize].is_flagged)
+            .count() as u32;
+
+        flags == self.tiles[position.1 as usize][position.0 as usize].adjacent_mines
+    }
+
+    /// The tiles around a position that are on the board.
+    pub fn neighbours(&self, position: (u32, u32)) -> Vec<(u32, u32)> {
+        (-1..=1)
+            .flat_map(|y| (-1..=1).map(move |x| (x, y)))
+            .filter(|offset| *offset != (0, 0))
+            .map(|(x, y)| (position.0 as i32 + x, position.1 as i32 + y))
+            .filter(|position| !self.is_out_of_bounds(*position))
+            .map(|(x, y)| (x as u32, y as u32))
+            .collect()
+    }
 
-        if !tile.is_revealed && !tile.is_flagged && self.placed_flag_count < self.number_of_mines {
-            tile.is_flagged = true;
-            self.placed_flag_count += 1;
+    /// Keeps digging the tiles around satisfied numbers until there are none left or the game
+    /// ends. Returns everything that was revealed. The whole pass is one move for undo.
+    pub fn dig_satisfied_tiles(&mut self) -> Vec<(u32, u32)> {
+        let mut revealed = vec![];
+        let mut saved = false;
+
+        while self.state == GameState::Playing && !self.paused {
+            let satisfied = self.satisfied_tiles();
+            if satisfied.is_empty() {
+                break;
+            }
+
+            if !saved {
+                self.save_snapshot();
+                saved = true;
+            }
+
+            for position in satisfied {
+                if self.state != GameState::Playing {
+                    break;
+                }
+                if let Ok(outcome) = self.dig_with_snapshot(position, false) {
+                    revealed.extend(outcome.revealed);
+                }
+            }
         }
+
+        revealed
     }
 
-    pub fn unflag(&mut self, position: (u32, u32)) {
-        if self.state != GameState::Playing {
+    /// Starts the game from a seed, so the same seed always gives the same mines and the same
+    /// opening. Returns the position of the first dig.
+    pub fn start_seeded(&mut self, seed: u64) -> Result<(u32, u32), GenerationError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let position = (rng.gen_range(0..self.width), rng.gen_range(0..self.height));
+
+        self.generate_mines(Some(position), &mut rng)?;
+        let outcome = self.begin(position);
+        self.emit_outcome(position, &outcome);
+        Ok(position)
+    }
+
+    /// Starts the game with the mines of another board of the same size, clearing the same
+    /// opening that board's first dig did.
+    pub fn start_with_layout(&mut self, layout: &[Vec<Tile>], first_dig: (u32, u32)) {
+        if self.state != GameState::NotStarted {
             return;
         }
 
-        self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
+        for (row, layout_row) in self.tiles.iter_mut().zip(layout) {
+            for (tile, layout_tile) in row.iter_mut().zip(layout_row) {
+                tile.is_mine = layout_tile.is_mine;
+                tile.adjacent_mines = layout_tile.adjacent_mines;
+            }
+        }
+
+        let outcome = self.begin(first_dig);
+        self.emit_outcome(first_dig, &outcome);
+    }
+
+    /// Time spent actually playing: every interval from starting or resuming the game up to the
+    /// next pause, or up to the last move if it hasn't been paused since.
+    pub fn active_time(&self) -> Duration {
+        self.active_time_until(self.last_move_time)
+    }
+
+    pub fn active_time_until(&self, end: DateTime<Utc>) -> Duration {
+        if self.paused {
+            return self.banked_time;
+        }
+        self.banked_time + (end - self.active_since).max(Duration::zero())
+    }
+
+    /// A copy of the game for people watching it. While the game is running, nothing about the
+    /// unrevealed tiles is copied apart from their flags, so no drawing of the copy can give away
+    /// where the mines are.
+    pub fn spectator_view(&self) -> Game {
+        let running = !matches!(self.state, GameState::Won | GameState::Lost);
+
+        let mut view = Game::new(self.width, self.height, self.number_of_mines);
+        view.tiles = self
+            .tiles
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Tile {
+                            is_revealed: false,
+                            is_flagged,
+                            ..
+                        } if running => Tile {
+                            is_flagged: *is_flagged,
+                            ..Tile::new()
+                        },
+                        tile => tile.clone(),
+                    })
+                    .collect()
+            })
+            .collect();
+        view.unmined_tiles = self.unmined_tiles;
+        view.placed_flag_count = self.placed_flag_count;
+        view.state = self.state;
+        view.time_started = self.time_started;
+        view.last_move_time = self.last_move_time;
+        view.move_count = self.move_count;
+        view.safety_policy = self.safety_policy;
+        view.paused = self.paused;
+        view.active_since = self.active_since;
+        view.banked_time = self.banked_time;
+        view.first_dig = self.first_dig;
+        view.letter_columns = self.letter_columns;
+        view
+    }
+
+    /// The last time the player did something that keeps the game alive: a move or resuming it.
+    pub fn last_activity(&self) -> DateTime<Utc> {
+        self.last_move_time.max(self.active_since)
+    }
+
+    pub fn pause(&mut self) -> Result<(), MoveError> {
+        match self.state {
+            GameState::NotStarted => return Err(MoveError::NotPlaying),
+            GameState::Won | GameState::Lost => return Err(MoveError::GameOver),
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => (),
+        }
+
+        let now = DateTime::<Utc>::from(SystemTime::now());
+        self.banked_time = self.banked_time + (now - self.active_since);
+        self.paused = true;
+        self.pause_count += 1;
+
+        self.emit(GameEventKind::Paused);
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<(), MoveError> {
+        if !self.paused {
+            return Err(MoveError::NotPaused);
+        }
+
+        self.active_since = DateTime::<Utc>::from(SystemTime::now());
+        self.paused = false;
+
+        self.emit(GameEventKind::Resumed);
+        Ok(())
+    }
+
+    pub fn check_position(&self, position: (u32, u32)) -> Result<(), MoveError> {
+        if position.0 >= self.width || position.1 >= self.height {
+            return Err(MoveError::OutOfBounds);
+        }
+        Ok(())
+    }
+
+    pub fn dig(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.dig_with_snapshot(position, true)
+    }
+
+    /// Digs a tile, saving the board for undo first unless the caller already did.
+    fn dig_with_snapshot(
+        &mut self,
+        position: (u32, u32),
+        snapshot: bool,
+    ) -> Result<MoveOutcome, MoveError> {
+        self.check_position(position)?;
+
+        let outcome = match &self.state {
+            GameState::NotStarted => self.start_dig(position, &mut rand::thread_rng())?,
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => {
+                let tile = &self.tiles[position.1 as usize][position.0 as usize];
+
+                if tile.is_flagged {
+                    return Err(MoveError::Flagged);
+                }
+                if tile.is_revealed {
+                    return Err(MoveError::AlreadyRevealed);
+                }
+
+                if snapshot {
+                    self.save_snapshot();
+                }
+                self.single_dig(position)
+            }
+            _ => return Err(MoveError::GameOver),
+        };
+
+        self.move_count += 1;
+        self.emit_outcome(position, &outcome);
+        Ok(outcome)
+    }
+
+    pub fn flag(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.check_position(position)?;
+
+        match self.state {
+            GameState::NotStarted => return Err(MoveError::NotStarted),
+            GameState::Won | GameState::Lost => return Err(MoveError::GameOver),
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => (),
+        }
+
         let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
 
+        if tile.is_revealed {
+            return Err(MoveError::AlreadyRevealed);
+        }
         if tile.is_flagged {
-            tile.is_flagged = false;
-            self.placed_flag_count -= 1
+            return Err(MoveError::AlreadyFlagged);
+        }
+        if self.placed_flag_count >= self.number_of_mines {
+            return Err(MoveError::FlagLimitReached);
+        }
+
+        self.save_snapshot();
+        let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
+        tile.is_flagged = true;
+        self.placed_flag_count += 1;
+        self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
+        self.move_count += 1;
+
+        let outcome = MoveOutcome {
+            flag_change: Some(FlagChange::Placed),
+            ..Default::default()
+        };
+        self.emit_outcome(position, &outcome);
+        Ok(outcome)
+    }
+
+    pub fn unflag(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.check_position(position)?;
+
+        match self.state {
+            GameState::NotStarted => return Err(MoveError::NotStarted),
+            GameState::Won | GameState::Lost => return Err(MoveError::GameOver),
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => (),
+        }
+
+        let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
+
+        if !tile.is_flagged {
+            return Err(MoveError::NotFlagged);
+        }
+
+        self.save_snapshot();
+        let tile = &mut self.tiles[position.1 as usize][position.0 as usize];
+        tile.is_flagged = false;
+        self.placed_flag_count -= 1;
+        self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
+        self.move_count += 1;
+
+        let outcome = MoveOutcome {
+            flag_change: Some(FlagChange::Removed),
+            ..Default::default()
+        };
+        self.emit_outcome(position, &outcome);
+        Ok(outcome)
+    }
+
+    /// Digs every unflagged tile around a number that already has enough flags around it.
+    /// A wrong flag means one of those tiles is a mine.
+    pub fn chord(&mut self, position: (u32, u32)) -> Result<MoveOutcome, MoveError> {
+        self.check_position(position)?;
+
+        match self.state {
+            GameState::NotStarted => return Err(MoveError::NotStarted),
+            GameState::Won | GameState::Lost => return Err(MoveError::GameOver),
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => (),
+        }
+
+        let tile = &self.tiles[position.1 as usize][position.0 as usize];
+
+        if !tile.is_revealed || tile.adjacent_mines == 0 {
+            return Err(MoveError::NotANumber);
+        }
+        if !self.is_satisfied(position) {
+            return Err(MoveError::NotSatisfied);
+        }
+
+        self.save_snapshot();
+        let mut outcome = MoveOutcome::default();
+
+        for neighbour in self.neighbours(position) {
+            let tile = &self.tiles[neighbour.1 as usize][neighbour.0 as usize];
+            if tile.is_revealed || tile.is_flagged {
+                continue;
+            }
+
+            let dug = self.single_dig(neighbour);
+            outcome.revealed.extend(dug.revealed);
+            if dug.new_state.is_some() {
+                outcome.new_state = dug.new_state;
+                break;
+            }
+        }
+
+        self.move_count += 1;
+        self.emit_outcome(position, &outcome);
+        Ok(outcome)
+    }
+
+    fn save_snapshot(&mut self) {
+        self.undo_snapshot = Some(Snapshot {
+            tiles: self.tiles.clone(),
+            unmined_tiles: self.unmined_tiles,
+            placed_flag_count: self.placed_flag_count,
+        });
+    }
+
+    /// Takes back the last dig, flag or chord. Only one move can be taken back, and never the
+    /// first dig or a move that ended the game.
+    pub fn undo(&mut self) -> Result<(), MoveError> {
+        if !self.allow_undo {
+            return Err(MoveError::UndoDisabled);
+        }
+
+        match self.state {
+            GameState::NotStarted => return Err(MoveError::NothingToUndo),
+            GameState::Won | GameState::Lost => return Err(MoveError::GameOver),
+            GameState::Playing if self.paused => return Err(MoveError::Paused),
+            GameState::Playing => (),
         }
+
+        let snapshot = self.undo_snapshot.take().ok_or(MoveError::NothingToUndo)?;
+
+        self.tiles = snapshot.tiles;
+        self.unmined_tiles = snapshot.unmined_tiles;
+        self.placed_flag_count = snapshot.placed_flag_count;
+        self.last_move_time = DateTime::<Utc>::from(SystemTime::now());
+        self.move_count += 1;
+
+        Ok(())
     }
 }
 
-pub struct GameDataKey;
+/// The difficulties difficulty_settings knows, from easiest to hardest.
+pub const DIFFICULTIES: &[&str] = &["easy", "medium", "hard"];
+
+pub fn difficulty_settings(difficulty: &str) -> Option<(u32, u32, u32)> {
+    match difficulty {
+        "easy" => Some((10, 8, 10)),
+        "medium" => Some((18, 14, 40)),
+        "hard" => Some((24, 20, 99)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mines(game: &Game) -> Vec<(usize, usize)> {
+        (0..game.tiles.len())
+            .flat_map(|y| (0..game.tiles[y].len()).map(move |x| (x, y)))
+            .filter(|(x, y)| game.tiles[*y][*x].is_mine)
+            .collect()
+    }
+
+    /// A game started from rows of `*` for mines and `.` for safe tiles, first dug at `first_dig`.
+    fn started(rows: &[&str], first_dig: (u32, u32)) -> Game {
+        let is_mine = |x: i64, y: i64| {
+            x >= 0
+                && y >= 0
+                && rows
+                    .get(y as usize)
+                    .and_then(|row| row.as_bytes().get(x as usize))
+                    == Some(&b'*')
+        };
+
+        let mut game = Game::new(rows[0].len() as u32, rows.len() as u32, 0);
+        let layout = (0..game.height as i64)
+            .map(|y| {
+                (0..game.width as i64)
+                    .map(|x| Tile {
+                        is_mine: is_mine(x, y),
+                        adjacent_mines: (y - 1..=y + 1)
+                            .flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                            .filter(|(nx, ny)| (*nx, *ny) != (x, y) && is_mine(*nx, *ny))
+                            .count() as u32,
+                        ..Tile::new()
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<Tile>>>();
+
+        game.number_of_mines = layout.iter().flatten().filter(|tile| tile.is_mine).count() as u32;
+        game.start_with_layout(&layout, first_dig);
+        game
+    }
+
+    fn revealed(game: &Game) -> Vec<Vec<bool>> {
+        game.tiles
+            .iter()
+            .map(|row| row.iter().map(|tile| tile.is_revealed).collect())
+            .collect()
+    }
+
+    #[test]
+    fn seeded_games_get_the_same_board() {
+        let mut first = Game::new(16, 16, 40);
+        let mut second = Game::new(16, 16, 40);
+
+        let first_dig = first.start_seeded(20240101).unwrap();
+
+        assert_eq!(second.start_seeded(20240101), Ok(first_dig));
+        assert_eq!(mines(&first).len(), 40);
+        assert_eq!(mines(&first), mines(&second));
+        assert_eq!(revealed(&first), revealed(&second));
+        assert_eq!(first.state, GameState::Playing);
+    }
+
+    #[test]
+    fn different_seeds_get_different_boards() {
+        let mut first = Game::new(16, 16, 40);
+        let mut second = Game::new(16, 16, 40);
+
+        first.start_seeded(20240101).unwrap();
+        second.start_seeded(20240102).unwrap();
+
+        assert_ne!(mines(&first), mines(&second));
+    }
 
-impl TypeMapKey for GameDataKey {
-    type Value = Arc<DashMap<u64, Game>>;
+    #[test]
+    fn seeded_boards_that_cant_fit_their_mines_are_not_started() {
+        let mut game = Game::new(3, 3, 9);
+
+        assert!(matches!(
+            game.start_seeded(1),
+            Err(GenerationError::TooManyMines { .. })
+        ));
+        assert_eq!(game.state, GameState::NotStarted);
+    }
+
+    #[test]
+    fn spectators_learn_nothing_about_unrevealed_tiles() {
+        let mut game = Game::new(16, 16, 40);
+        game.start_seeded(20240101).unwrap();
+        let (x, y) = mines(&game)[0];
+        game.flag((x as u32, y as u32)).unwrap();
+
+        let view = game.spectator_view();
+
+        assert!(mines(&view).is_empty());
+        assert_eq!(revealed(&view), revealed(&game));
+        assert!(view.tiles[y][x].is_flagged);
+        for (row, view_row) in game.tiles.iter().zip(&view.tiles) {
+            for (tile, view_tile) in row.iter().zip(view_row) {
+                if tile.is_revealed {
+                    assert_eq!(view_tile.adjacent_mines, tile.adjacent_mines);
+                } else {
+                    assert_eq!(view_tile.adjacent_mines, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn spectators_see_the_mines_once_the_game_is_over() {
+        let mut game = Game::new(16, 16, 40);
+        game.start_seeded(20240101).unwrap();
+        let (x, y) = mines(&game)[0];
+        game.dig((x as u32, y as u32)).unwrap();
+
+        let view = game.spectator_view();
+
+        assert_eq!(view.state, GameState::Lost);
+        assert_eq!(mines(&view), mines(&game));
+    }
+
+    #[test]
+    fn safety_policy_names_parse() {
+        assert_eq!(
+            SafetyPolicy::parse("nosafety"),
+            Some(SafetyPolicy::Unprotected)
+        );
+        assert_eq!(
+            SafetyPolicy::parse("safetile"),
+            Some(SafetyPolicy::SafeTile)
+        );
+        assert_eq!(SafetyPolicy::parse("opening"), Some(SafetyPolicy::Opening));
+        assert_eq!(
+            SafetyPolicy::parse("radius=4"),
+            Some(SafetyPolicy::Radius(4))
+        );
+        assert_eq!(SafetyPolicy::parse("radius=0"), None);
+        assert_eq!(SafetyPolicy::parse("radius=5"), None);
+        assert_eq!(SafetyPolicy::parse("radius=two"), None);
+        assert_eq!(SafetyPolicy::parse("safe"), None);
+    }
+
+    #[test]
+    fn safety_policies_protect_their_area() {
+        let dug = (5, 5);
+
+        assert!(!SafetyPolicy::Unprotected.is_protected(dug, dug));
+        assert!(SafetyPolicy::SafeTile.is_protected(dug, dug));
+        assert!(!SafetyPolicy::SafeTile.is_protected((5, 6), dug));
+        assert!(SafetyPolicy::Opening.is_protected((6, 4), dug));
+        assert!(!SafetyPolicy::Opening.is_protected((7, 5), dug));
+        assert!(SafetyPolicy::Radius(2).is_protected((6, 6), dug));
+        assert!(!SafetyPolicy::Radius(2).is_protected((7, 5), dug));
+        assert!(SafetyPolicy::Radius(3).is_protected((7, 6), dug));
+    }
+
+    #[test]
+    fn the_first_dig_keeps_protected_tiles_clear() {
+        for _ in 0..20 {
+            let mut game = Game::new(9, 9, 30);
+            game.safety_policy = SafetyPolicy::Opening;
+            game.dig((0, 4)).unwrap();
+
+            assert_eq!(mines(&game).len(), 30);
+            assert!(mines(&game)
+                .iter()
+                .all(|(x, y)| *x > 1 || y.abs_diff(4) > 1));
+        }
+    }
+
+    #[test]
+    fn moves_before_the_first_dig_are_refused() {
+        let mut game = Game::new(3, 2, 2);
+
+        assert_eq!(game.flag((0, 0)).unwrap_err(), MoveError::NotStarted);
+        assert_eq!(game.unflag((0, 0)).unwrap_err(), MoveError::NotStarted);
+        assert_eq!(game.chord((0, 0)).unwrap_err(), MoveError::NotStarted);
+        assert_eq!(game.undo().unwrap_err(), MoveError::NothingToUndo);
+        assert_eq!(game.dig((3, 0)).unwrap_err(), MoveError::OutOfBounds);
+    }
+
+    #[test]
+    fn moves_on_the_wrong_tile_are_refused() {
+        let mut game = started(&["*..", "..*"], (0, 1));
+
+        assert_eq!(game.dig((0, 1)).unwrap_err(), MoveError::AlreadyRevealed);
+        assert_eq!(game.flag((0, 1)).unwrap_err(), MoveError::AlreadyRevealed);
+        assert_eq!(game.unflag((1, 0)).unwrap_err(), MoveError::NotFlagged);
+        assert_eq!(game.chord((1, 0)).unwrap_err(), MoveError::NotANumber);
+        assert_eq!(game.chord((0, 1)).unwrap_err(), MoveError::NotSatisfied);
+        assert_eq!(game.flag((0, 2)).unwrap_err(), MoveError::OutOfBounds);
+
+        game.flag((0, 0)).unwrap();
+        assert_eq!(game.flag((0, 0)).unwrap_err(), MoveError::AlreadyFlagged);
+        assert_eq!(game.dig((0, 0)).unwrap_err(), MoveError::Flagged);
+
+        game.flag((2, 0)).unwrap();
+        assert_eq!(game.flag((1, 1)).unwrap_err(), MoveError::FlagLimitReached);
+    }
+
+    #[test]
+    fn moves_after_the_game_ends_are_refused() {
+        let mut game = started(&["*..", "..*"], (0, 1));
+
+        let outcome = game.dig((2, 1)).unwrap();
+
+        assert_eq!(outcome.new_state, Some(GameState::Lost));
+        assert_eq!(game.dig((1, 0)).unwrap_err(), MoveError::GameOver);
+        assert_eq!(game.flag((1, 0)).unwrap_err(), MoveError::GameOver);
+        assert_eq!(game.chord((0, 1)).unwrap_err(), MoveError::GameOver);
+        assert_eq!(game.undo().unwrap_err(), MoveError::GameOver);
+    }
+
+    #[test]
+    fn only_running_games_pause_and_resume() {
+        let mut game = Game::new(3, 2, 2);
+        assert_eq!(game.pause().unwrap_err(), MoveError::NotPlaying);
+        assert_eq!(game.resume().unwrap_err(), MoveError::NotPaused);
+
+        let mut game = started(&["*..", "..*"], (0, 1));
+        game.pause().unwrap();
+        assert_eq!(game.pause().unwrap_err(), MoveError::Paused);
+        assert_eq!(game.dig((1, 0)).unwrap_err(), MoveError::Paused);
+        assert_eq!(game.flag((0, 0)).unwrap_err(), MoveError::Paused);
+
+        game.resume().unwrap();
+        assert_eq!(game.resume().unwrap_err(), MoveError::NotPaused);
+        game.dig((2, 1)).unwrap();
+        assert_eq!(game.pause().unwrap_err(), MoveError::GameOver);
+    }
+
+    #[test]
+    fn active_time_leaves_out_pauses() {
+        let mut game = started(&["*..", "..*"], (0, 1));
+        game.active_since = game.active_since - Duration::minutes(10);
+
+        game.pause().unwrap();
+        let now = DateTime::<Utc>::from(SystemTime::now());
+        assert_eq!(game.active_time().num_minutes(), 10);
+        assert_eq!(
+            game.active_time_until(now + Duration::hours(1))
+                .num_minutes(),
+            10
+        );
+
+        game.resume().unwrap();
+        let now = DateTime::<Utc>::from(SystemTime::now());
+        assert_eq!(
+            game.active_time_until(now + Duration::minutes(5))
+                .num_minutes(),
+            15
+        );
+        assert_eq!(game.pause_count, 1);
+    }
+
+    #[test]
+    fn undo_takes_back_a_whole_auto_dig_pass() {
+        let mut game = started(&["*..", "..*"], (0, 1));
+        game.flag((0, 0)).unwrap();
+        let before = revealed(&game);
+
+        let mut dug = game.dig_satisfied_tiles();
+        dug.sort();
+
+        assert_eq!(dug, vec![(1, 0), (1, 1)]);
+        game.undo().unwrap();
+        assert_eq!(revealed(&game), before);
+        assert!(game.tiles[0][0].is_flagged);
+    }
 }
@@ -0,0 +1,316 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::Arc,
+    sync::Mutex,
+};
+
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+use serenity::prelude::TypeMapKey;
+
+use crate::board;
+use crate::data::AppConfiguration;
+use crate::game::{self, SafetyPolicy};
+use crate::solver::Tier;
+use crate::store;
+
+/// The prefix used where a server hasn't picked its own.
+pub const DEFAULT_PREFIX: &str = "~";
+
+const GUILD_CONFIGS_FILE: &str = "guilds.json";
+
+/// The widest or tallest board a preset or limit may ask for.
+pub const MAX_BOARD_SIDE: u32 = 50;
+
+/// A board size a server saved under its own name.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Preset {
+    pub width: u32,
+    pub height: u32,
+    pub mines: u32,
+}
+
+impl Preset {
+    pub fn settings(&self) -> (u32, u32, u32) {
+        (self.width, self.height, self.mines)
+    }
+
+    /// Checks that the preset makes a playable board and that its name doesn't clash with the
+    /// other words startgame understands.
+    pub fn check(&self, name: &str) -> Result<(), PresetError> {
+        if name.contains(char::is_whitespace)
+            || board::find_variant(name).is_some()
+            || SafetyPolicy::parse(name).is_some()
+            || Tier::parse(name).is_some()
+        {
+            Err(PresetError::Name)
+        } else if !(1..=MAX_BOARD_SIDE).contains(&self.width)
+            || !(1..=MAX_BOARD_SIDE).contains(&self.height)
+        {
+            Err(PresetError::Size)
+        } else if self.mines == 0 || self.mines >= self.width * self.height {
+            Err(PresetError::Mines)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum PresetError {
+    Name,
+    Size,
+    Mines,
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Name => write!(f, "That name is already a startgame option!"),
+            PresetError::Size => write!(
+                f,
+                "Boards can be between 1 and {} tiles wide and tall!",
+                MAX_BOARD_SIDE
+            ),
+            PresetError::Mines => write!(f, "A board needs at least one mine and one safe tile!"),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+/// The biggest boards a server allows. Missing values aren't limited.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Limits {
+    #[serde(default)]
+    pub max_width: Option<u32>,
+    #[serde(default)]
+    pub max_height: Option<u32>,
+    #[serde(default)]
+    pub max_mines: Option<u32>,
+}
+
+/// Which limit a board broke, with the largest value the server allows.
+#[derive(Debug, PartialEq)]
+pub enum LimitError {
+    Width(u32),
+    Height(u32),
+    Mines(u32),
+}
+
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitError::Width(max) => {
+                write!(f, "Boards in this server can be at most {} wide!", max)
+            }
+            LimitError::Height(max) => {
+                write!(f, "Boards in this server can be at most {} tall!", max)
+            }
+            LimitError::Mines(max) => {
+                write!(f, "Boards in this server can have at most {} mines!", max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LimitError {}
+
+impl Limits {
+    /// The smaller of each limit, so neither set of limits can be exceeded.
+    pub fn tightest(&self, other: &Limits) -> Limits {
+        let tightest = |a: Option<u32>, b: Option<u32>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        Limits {
+            max_width: tightest(self.max_width, other.max_width),
+            max_height: tightest(self.max_height, other.max_height),
+            max_mines: tightest(self.max_mines, other.max_mines),
+        }
+    }
+
+    pub fn check(&self, (width, height, mines): (u32, u32, u32)) -> Result<(), LimitError> {
+        match (self.max_width, self.max_height, self.max_mines) {
+            (Some(max), _, _) if width > max => Err(LimitError::Width(max)),
+            (_, Some(max), _) if height > max => Err(LimitError::Height(max)),
+            (_, _, Some(max)) if mines > max => Err(LimitError::Mines(max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// How the bot behaves in one server. Anything missing from guilds.json keeps its default.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct GuildConfig {
+    /// Post a new board message after every move instead of editing the last one.
+    #[serde(default)]
+    pub repost_boards: bool,
+    /// Replaces the default prefix for text commands.
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// The channels the bot answers in. Empty means every channel.
+    #[serde(default)]
+    pub allowed_channels: Vec<u64>,
+    /// Extra difficulties, which take priority over the built-in ones.
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+    #[serde(default)]
+    pub limits: Limits,
+}
+
+impl GuildConfig {
+    pub fn prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or(DEFAULT_PREFIX)
+    }
+
+    pub fn allows_channel(&self, channel_id: u64) -> bool {
+        self.allowed_channels.is_empty() || self.allowed_channels.contains(&channel_id)
+    }
+
+    /// The width, height and number of mines of a preset or built-in difficulty.
+    pub fn difficulty_settings(&self, difficulty: &str) -> Option<(u32, u32, u32)> {
+        match self.presets.get(difficulty) {
+            Some(preset) => Some(preset.settings()),
+            None => game::difficulty_settings(difficulty),
+        }
+    }
+
+    /// Every difficulty that can be picked here, built-in ones first.
+    pub fn difficulty_names(&self) -> Vec<&str> {
+        game::DIFFICULTIES
+            .iter()
+            .copied()
+            .filter(|name| !self.presets.contains_key(*name))
+            .chain(self.presets.keys().map(String::as_str))
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct GuildConfigStore {
+    guilds: Mutex<HashMap<u64, GuildConfig>>,
+    /// Presets and limits from config.json, which apply to every server.
+    default_presets: BTreeMap<String, Preset>,
+    limits: Limits,
+}
+
+impl GuildConfigStore {
+    /// The config of a server, or the defaults for direct messages.
+    pub fn get_for(&self, guild_id: Option<GuildId>) -> GuildConfig {
+        match guild_id {
+            Some(guild_id) => self.get(guild_id.0),
+            None => self.with_defaults(GuildConfig::default()),
+        }
+    }
+
+    pub fn get(&self, guild_id: u64) -> GuildConfig {
+        let config = self
+            .guilds
+            .lock()
+            .unwrap()
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_default();
+
+        self.with_defaults(config)
+    }
+
+    fn with_defaults(&self, mut config: GuildConfig) -> GuildConfig {
+        for (name, preset) in &self.default_presets {
+            config.presets.entry(name.clone()).or_insert(*preset);
+        }
+        config.limits = config.limits.tightest(&self.limits);
+        config
+    }
+
+    pub fn update(&self, guild_id: u64, change: impl FnOnce(&mut GuildConfig)) {
+        change(self.guilds.lock().unwrap().entry(guild_id).or_default());
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        store::save(GUILD_CONFIGS_FILE, &*self.guilds.lock().unwrap())
+    }
+}
+
+pub fn load_guild_configs(config: &AppConfiguration) -> GuildConfigStore {
+    GuildConfigStore {
+        guilds: Mutex::new(store::load(GUILD_CONFIGS_FILE)),
+        default_presets: config.default_presets.clone(),
+        limits: config.limits.clone(),
+    }
+}
+
+pub struct GuildConfigDataKey;
+
+impl TypeMapKey for GuildConfigDataKey {
+    type Value = Arc<GuildConfigStore>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(width: u32, height: u32, mines: u32) -> Preset {
+        Preset {
+            width,
+            height,
+            mines,
+        }
+    }
+
+    #[test]
+    fn presets_need_a_free_name_and_a_playable_board() {
+        assert_eq!(preset(8, 8, 10).check("small"), Ok(()));
+        assert_eq!(preset(50, 50, 2499).check("huge"), Ok(()));
+
+        for name in ["two words", "coop", "opening", "radius=2", "guessing"] {
+            assert_eq!(preset(8, 8, 10).check(name), Err(PresetError::Name));
+        }
+
+        assert_eq!(preset(0, 8, 1).check("thin"), Err(PresetError::Size));
+        assert_eq!(preset(51, 8, 10).check("wide"), Err(PresetError::Size));
+        assert_eq!(preset(8, 8, 0).check("empty"), Err(PresetError::Mines));
+        assert_eq!(preset(8, 8, 64).check("full"), Err(PresetError::Mines));
+    }
+
+    #[test]
+    fn limits_report_the_first_one_broken() {
+        let limits = Limits {
+            max_width: Some(20),
+            max_height: None,
+            max_mines: Some(50),
+        };
+
+        assert_eq!(limits.check((20, 40, 50)), Ok(()));
+        assert_eq!(limits.check((21, 40, 60)), Err(LimitError::Width(20)));
+        assert_eq!(limits.check((10, 10, 51)), Err(LimitError::Mines(50)));
+        assert_eq!(Limits::default().check((50, 50, 2499)), Ok(()));
+    }
+
+    #[test]
+    fn tightest_limits_keep_the_smaller_of_each() {
+        let global = Limits {
+            max_width: Some(30),
+            max_height: Some(30),
+            max_mines: None,
+        };
+        let server = Limits {
+            max_width: Some(40),
+            max_height: Some(16),
+            max_mines: Some(99),
+        };
+
+        let tightest = global.tightest(&server);
+
+        assert_eq!(tightest.max_width, Some(30));
+        assert_eq!(tightest.max_height, Some(16));
+        assert_eq!(tightest.max_mines, Some(99));
+        assert_eq!(
+            Limits::default().tightest(&Limits::default()).max_width,
+            None
+        );
+    }
+}
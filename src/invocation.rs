@@ -0,0 +1,24 @@
+use serenity::model::channel::Message;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::user::User;
+
+/// Who ran a command and where. Prefix and slash commands are both turned into one of these, so
+/// the code that runs a command doesn't care which way it was typed.
+pub struct Invocation {
+    pub author: User,
+    pub channel_id: ChannelId,
+    pub guild_id: Option<GuildId>,
+    /// Users the command was aimed at, such as the opponent in a Flagstorm game.
+    pub mentions: Vec<User>,
+}
+
+impl From<&Message> for Invocation {
+    fn from(msg: &Message) -> Self {
+        Invocation {
+            author: msg.author.clone(),
+            channel_id: msg.channel_id,
+            guild_id: msg.guild_id,
+            mentions: msg.mentions.clone(),
+        }
+    }
+}
@@ -0,0 +1,5 @@
+//! The parts of the bot that don't need Discord, shared with the benchmark binary.
+
+pub mod events;
+pub mod game;
+pub mod solver;
@@ -0,0 +1,24 @@
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Prints log records to stdout, which is where the bot's output has always gone.
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        println!("[{}] {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+pub fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
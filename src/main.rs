@@ -1,53 +1,159 @@
-use dashmap::DashMap;
-use drawing::{DefaultMinesweeperDrawer, MinesweeperDrawer};
-use game::{Game, GameDataKey, GameState};
+use board::{Board, FocusDataKey, GameDataKey, GameKey, GameSetup, Summary, Variant};
+use board_message::BoardRender;
+use chrono::{DateTime, Utc};
+use controls::ControlDataKey;
+use daily::{DailyAttemptListener, DailyDataKey, DailyOutcome};
+use dashmap::{mapref::entry::Entry, DashMap};
+use game::{Game, GameState, MoveError, SafetyPolicy};
+use guilds::{GuildConfig, GuildConfigDataKey, Preset};
+use invocation::Invocation;
+use minesweeper::{events, game, solver};
+use moves::{MoveKind, PlannedMove};
+use race::{Challenge, ChallengeDataKey, RaceDataKey, RaceEntry, RaceFinish, RaceLayout};
 use serenity::async_trait;
 use serenity::client::{Client, Context, EventHandler};
-use serenity::framework::standard::macros::{command, group};
+use serenity::framework::standard::macros::{command, group, hook};
 use serenity::framework::standard::Args;
 use serenity::framework::standard::{CommandResult, StandardFramework};
-use serenity::http::AttachmentType;
+use serenity::http::{AttachmentType, Http};
 use serenity::model::channel::Message;
-use serenity::model::id::ChannelId;
+use serenity::model::gateway::Ready;
+use serenity::model::id::{ChannelId, GuildId};
+use serenity::model::interactions::Interaction;
+use serenity::prelude::TypeMap;
+use settings::SettingsDataKey;
+use solver::Tier;
+use spectate::SpectatorDataKey;
 use std::borrow::Cow;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
 
 #[macro_use]
 extern crate lazy_static;
 
+mod board;
+mod board_message;
+mod controls;
+mod coop;
+mod daily;
 mod data;
 mod drawing;
-mod game;
+mod flags;
+mod guilds;
+mod invocation;
+mod logging;
+mod moves;
+mod race;
+mod reaper;
+mod settings;
+mod slash;
+mod spectate;
+mod spoiler;
+mod store;
 mod text;
 
 #[group]
-#[commands(startgame, dig, flag, unflag, help, resend, stopgame)]
+#[commands(
+    startgame,
+    joingame,
+    dig,
+    play,
+    chord,
+    undo,
+    flag,
+    unflag,
+    help,
+    resend,
+    stopgame,
+    games,
+    switchgame,
+    watch,
+    unwatch,
+    guildgames,
+    forcestop,
+    purgestats,
+    spoilerboard,
+    daily,
+    challenge,
+    accept,
+    flagstorm,
+    pause,
+    resume,
+    autodig,
+    notation,
+    boardmessages,
+    prefix,
+    botchannels,
+    preset,
+    limits
+)]
 struct General;
 
-struct Handler;
+struct Handler {
+    /// Whether slash commands should be registered once the bot is connected.
+    slash_commands: bool,
+    /// Set by the first `ready`, so reconnecting doesn't register the same commands again.
+    registered: AtomicBool,
+}
 
 #[async_trait]
-impl EventHandler for Handler {}
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, _ready: Ready) {
+        if self.slash_commands && !self.registered.swap(true, Ordering::SeqCst) {
+            if let Err(e) = slash::register(&ctx.http).await {
+                log::error!("Couldn't register slash commands: {}", e);
+            }
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => slash::run(&ctx, &command).await,
+            Interaction::Autocomplete(autocomplete) => {
+                slash::autocomplete(&ctx, &autocomplete).await
+            }
+            Interaction::MessageComponent(component) => controls::handle(&ctx, &component).await,
+            _ => (),
+        }
+    }
+}
 
 #[tokio::main]
 
 async fn main() {
     let config = data::load_configuration().unwrap_or_else(|e| {
-        println!("Couldn't parse config.json: {}", e);
-        panic!();
+        println!("Invalid configuration: {}", e);
+        std::process::exit(1);
     });
 
-    let running_games = Arc::new(DashMap::<u64, Game>::new());
+    logging::init(config.log_level());
+
+    let running_games = Arc::new(DashMap::<GameKey, Box<dyn Board>>::new());
+    let running_races = Arc::new(DashMap::<u64, RaceEntry>::new());
+    let pending_challenges = Arc::new(DashMap::<u64, Challenge>::new());
+
+    let daily_results = daily::load_daily_results();
+    let user_settings = settings::load_settings();
+    let guild_configs = guilds::load_guild_configs(config);
 
     let framework = StandardFramework::new()
-        .configure(|c| c.prefix("~"))
+        .configure(|c| c.prefix("").dynamic_prefix(guild_prefix))
+        .before(allowed_channel)
         .group(&GENERAL_GROUP);
 
-    let mut client = Client::builder(&config.token)
-        .event_handler(Handler)
-        .framework(framework)
-        .await
-        .expect("Error creating client");
+    let mut client_builder = Client::builder(&config.token)
+        .event_handler(Handler {
+            slash_commands: config.application_id.is_some(),
+            registered: AtomicBool::new(false),
+        })
+        .framework(framework);
+
+    if let Some(application_id) = config.application_id {
+        client_builder = client_builder.application_id(application_id);
+    }
+
+    let mut client = client_builder.await.expect("Error creating client");
 
     client
         .data
@@ -55,61 +161,220 @@ async fn main() {
         .await
         .insert::<GameDataKey>(Arc::clone(&running_games));
 
+    client
+        .data
+        .write()
+        .await
+        .insert::<FocusDataKey>(Arc::new(DashMap::new()));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<SpectatorDataKey>(Arc::new(DashMap::new()));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<RaceDataKey>(Arc::clone(&running_races));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<ChallengeDataKey>(Arc::clone(&pending_challenges));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<DailyDataKey>(Arc::new(daily_results));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<SettingsDataKey>(Arc::new(user_settings));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<GuildConfigDataKey>(Arc::new(guild_configs));
+
+    client
+        .data
+        .write()
+        .await
+        .insert::<ControlDataKey>(Arc::new(DashMap::new()));
+
+    tokio::spawn(reaper::run(
+        Arc::clone(&client.data),
+        Arc::clone(&client.cache_and_http.http),
+        reaper::IdleLimits {
+            warning: chrono::Duration::minutes(config.idle_warning_minutes),
+            close: chrono::Duration::minutes(config.idle_close_minutes),
+        },
+    ));
+
     if let Err(why) = client.start().await {
-        println!("An error occurred while running the client: {:?}", why);
+        log::error!("An error occurred while running the client: {:?}", why);
     }
 }
 
+/// The commands that still work outside a server's allowed channels, so admins can fix them.
+const CONFIG_COMMANDS: &[&str] = &["botchannels", "prefix"];
+
+#[hook]
+async fn guild_prefix(ctx: &Context, msg: &Message) -> Option<String> {
+    Some(guild_config(ctx, msg.guild_id).await.prefix().to_string())
+}
+
+#[hook]
+async fn allowed_channel(ctx: &Context, msg: &Message, command_name: &str) -> bool {
+    CONFIG_COMMANDS.contains(&command_name)
+        || guild_config(ctx, msg.guild_id)
+            .await
+            .allows_channel(msg.channel_id.0)
+}
+
+async fn guild_config(ctx: &Context, guild_id: Option<GuildId>) -> GuildConfig {
+    ctx.data
+        .read()
+        .await
+        .get::<GuildConfigDataKey>()
+        .unwrap()
+        .get_for(guild_id)
+}
+
 #[command]
 async fn startgame(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let author = &msg.author;
-
-    if author.bot {
+    if msg.author.bot {
         return Ok(());
     }
 
-    if args.len() != 1 {
-        msg.channel_id
+    run_startgame(ctx, &Invocation::from(msg), args.message()).await;
+
+    Ok(())
+}
+
+/// Starts a game from the words after startgame: an optional mode, then the difficulty and its
+/// options. Mentions are left to the invocation.
+async fn run_startgame(ctx: &Context, invocation: &Invocation, args: &str) {
+    let args = args.to_ascii_lowercase();
+    let mut args = args
+        .split_whitespace()
+        .filter(|arg| !arg.starts_with("<@"))
+        .collect::<Vec<_>>();
+
+    let name = args
+        .iter()
+        .position(|arg| arg.starts_with("name="))
+        .map(|index| args.remove(index).trim_start_matches("name="));
+
+    if let Some(name) = name.filter(|name| !is_valid_game_name(name)) {
+        invocation
+            .channel_id
             .say(
                 &ctx.http,
-                "Usage:\nstartgame easy\nstartgame medium\nstartgame hard",
+                format!(
+                    "{} can't be used as a game name! Names can be up to {} letters, numbers, - or _, and can't be {} or {}.",
+                    name,
+                    MAX_GAME_NAME_LENGTH,
+                    daily::DAILY_GAME_NAME,
+                    race::RACE_GAME_NAME
+                ),
             )
             .await
             .ok();
-        return Ok(());
+        return;
     }
 
-    let args = args.message().to_ascii_lowercase();
+    let variant = match args.first().and_then(|name| board::find_variant(name)) {
+        Some(variant) => {
+            args.remove(0);
+            variant
+        }
+        None => &board::VARIANTS[0],
+    };
 
-    let game_settings = match args.as_str() {
-        "easy" => (10, 8, 10),
-        "medium" => (18, 14, 40),
-        "hard" => (24, 20, 99),
-        _ => {
-            msg.channel_id
+    let guild_config = guild_config(ctx, invocation.guild_id).await;
+
+    let setup = match parse_game_options(&args, &guild_config) {
+        Some(setup) => setup,
+        None => {
+            invocation
+                .channel_id
                 .say(
                     &ctx.http,
-                    "Usage:\nstartgame easy\nstartgame medium\nstartgame hard",
+                    format!(
+                        "Usage:\n{}\n\nDifficulties: {}\nAdd nosafety, safetile, opening or radius=1 to {} after the difficulty to change how the first dig is protected.\nAdd trivial, logical, hardlogical or guessing to get a board of that difficulty rating.\nAdd name=[name] to start a named game next to the ones you already have in this channel.",
+                        board::VARIANTS
+                            .iter()
+                            .map(|variant| variant.usage)
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        guild_config.difficulty_names().join(", "),
+                        SafetyPolicy::MAX_RADIUS
+                    ),
                 )
                 .await
                 .ok();
-            return Ok(());
+            return;
         }
     };
 
-    let data = ctx.data.read().await;
-    let game_data = data.get::<GameDataKey>().unwrap();
+    start_game(ctx, invocation, variant, setup, name).await;
+}
+
+const MAX_GAME_NAME_LENGTH: usize = 20;
+/// What switchgame calls the channel's shared game.
+const SHARED_GAME_NAME: &str = "shared";
+
+fn is_valid_game_name(name: &str) -> bool {
+    (1..=MAX_GAME_NAME_LENGTH).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && name != daily::DAILY_GAME_NAME
+        && name != SHARED_GAME_NAME
+        && name != race::RACE_GAME_NAME
+}
+
+/// Makes a game the one a player's moves in its channel go to.
+fn focus_game(data: &TypeMap, player: u64, key: &GameKey) {
+    data.get::<FocusDataKey>()
+        .unwrap()
+        .insert((player, key.channel()), key.clone());
+}
 
-    if game_data.contains_key(&author.id.0) {
-        msg.channel_id.say(&ctx.http, "You already have a running game!\nUse the command stopgame to end your current game if you would like to end it.\nUse the command resend if you would like to see your current progress.").await.ok();
+#[command]
+async fn joingame(ctx: &Context, msg: &Message) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
         return Ok(());
     }
 
-    let game = Game::new(game_settings.0, game_settings.1, game_settings.2);
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
 
-    send_game_render(ctx, msg.channel_id, &game).await.ok();
+    let key = GameKey::Channel(msg.channel_id.0);
+    let joined = game_data
+        .get_mut(&key)
+        .and_then(|mut board| board.join(author.id.0, &author.name));
+
+    if joined.is_some() {
+        focus_game(&data, author.id.0, &key);
+    }
 
-    game_data.insert(author.id.0, game);
+    let reply = match joined {
+        Some(true) => "You joined the co-op game! Your moves in this channel go to it until you use the command switchgame.",
+        Some(false) => "You're already part of this co-op game.",
+        None => "There's no co-op game in this channel! Use the command startgame coop [difficulty] to start one.",
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
 
     Ok(())
 }
@@ -125,78 +390,57 @@ async fn dig(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let coordinates = process_coordinates(&args);
 
     if coordinates.is_none() {
-        msg.channel_id.say(&ctx.http, "Usage: dig X Y").await.ok();
+        msg.channel_id
+            .say(&ctx.http, "Usage: dig X Y, or dig C7 with letter columns")
+            .await
+            .ok();
         return Ok(());
     }
 
-    let coordinates = coordinates.unwrap();
+    let planned = PlannedMove {
+        kind: MoveKind::Dig,
+        coordinates: coordinates.unwrap(),
+    };
+    apply_moves(ctx, &Invocation::from(msg), &[planned], vec![]).await;
 
-    let data = ctx.data.read().await;
-    let game_data_map = data.get::<GameDataKey>().unwrap();
-    let game_data = game_data_map.get_mut(&author.id.0);
-
-    if let Some(mut game) = game_data {
-        if coordinates.0 == 0
-            || coordinates.1 == 0
-            || coordinates.0 > game.width
-            || coordinates.1 > game.height
-        {
-            msg.channel_id
-                .say(&ctx.http, "Coordinates out of bounds!")
-                .await
-                .ok();
-            return Ok(());
-        }
+    Ok(())
+}
+
+#[command]
+async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
 
-        game.dig((coordinates.0 - 1, coordinates.1 - 1));
+    if author.bot {
+        return Ok(());
+    }
 
-        send_game_render(ctx, msg.channel_id, &game).await.ok();
+    let mut moves = vec![];
+    let mut report = vec![];
 
-        if game.state == GameState::Lost || game.state == GameState::Won {
-            msg.channel_id
-                .send_message(&ctx.http, |m| {
-                    m.add_embed(|embed| {
-                        let difference = game.last_move_time - game.time_started;
-                        let minutes = difference.num_minutes();
-                        let seconds = difference.num_seconds() - difference.num_minutes() * 60;
-                        embed.title("Game Summary");
-                        embed.description(format!(
-                            "Game {} in {} minute{} and {} second{}",
-                            if game.state == GameState::Won {
-                                "won"
-                            } else {
-                                "lost"
-                            },
-                            minutes,
-                            if minutes == 1 { "" } else { "s" },
-                            seconds,
-                            if seconds == 1 { "" } else { "s" }
-                        ));
-                        embed.field(
-                            "Grid Size",
-                            format!("{} by {}", game.width, game.height),
-                            true,
-                        );
-                        embed.field("Mine Count", format!("{}", game.number_of_mines), true);
-                        embed
-                    });
-                    m
-                })
-                .await
-                .unwrap();
-            drop(game);
-            game_data_map.remove(&msg.author.id.0);
+    for text in args.message().split_whitespace() {
+        match PlannedMove::parse(text) {
+            Some(planned) => moves.push(planned),
+            None => report.push(format!("`{}`: not a move", text)),
         }
-        Ok(())
-    } else {
-        msg.channel_id.say(
-            &ctx.http,
-            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
-        )
-        .await
-        .ok();
+    }
+
+    if moves.is_empty() || moves.len() > moves::MAX_MOVES {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "Usage: play d3,4 f7,2 u7,2 ...\nUp to {} moves, each d to dig, f to flag, u to unflag or c to chord followed by X,Y or a column letter and row like C7. They're played in order, stopping if a dig hits a mine.",
+                    moves::MAX_MOVES
+                ),
+            )
+            .await
+            .ok();
         return Ok(());
     }
+
+    apply_moves(ctx, &Invocation::from(msg), &moves, report).await;
+
+    Ok(())
 }
 
 #[command]
@@ -210,43 +454,20 @@ async fn flag(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let coordinates = process_coordinates(&args);
 
     if coordinates.is_none() {
-        msg.channel_id.say(&ctx.http, "Usage: dig X Y").await.ok();
+        msg.channel_id
+            .say(&ctx.http, "Usage: flag X Y, or flag C7 with letter columns")
+            .await
+            .ok();
         return Ok(());
     }
 
-    let coordinates = coordinates.unwrap();
-
-    let data = ctx.data.read().await;
-    let game_data = data.get::<GameDataKey>().unwrap();
-    let game_data = game_data.get_mut(&author.id.0);
-
-    if let Some(mut game) = game_data {
-        if coordinates.0 == 0
-            || coordinates.1 == 0
-            || coordinates.0 > game.width
-            || coordinates.1 > game.height
-        {
-            msg.channel_id
-                .say(&ctx.http, "Coordinates out of bounds!")
-                .await
-                .ok();
-            return Ok(());
-        }
-
-        game.flag((coordinates.0 - 1, coordinates.1 - 1));
-
-        send_game_render(ctx, msg.channel_id, &game).await.ok();
+    let planned = PlannedMove {
+        kind: MoveKind::Flag,
+        coordinates: coordinates.unwrap(),
+    };
+    apply_moves(ctx, &Invocation::from(msg), &[planned], vec![]).await;
 
-        Ok(())
-    } else {
-        msg.channel_id.say(
-            &ctx.http,
-            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
-        )
-        .await
-        .ok();
-        return Ok(());
-    }
+    Ok(())
 }
 
 #[command]
@@ -260,128 +481,2118 @@ async fn unflag(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let coordinates = process_coordinates(&args);
 
     if coordinates.is_none() {
-        msg.channel_id.say(&ctx.http, "Usage: dig X Y").await.ok();
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: unflag X Y, or unflag C7 with letter columns",
+            )
+            .await
+            .ok();
         return Ok(());
     }
 
-    let coordinates = coordinates.unwrap();
-
-    let data = ctx.data.read().await;
-    let game_data = data.get::<GameDataKey>().unwrap();
-    let game_data = game_data.get_mut(&author.id.0);
-
-    if let Some(mut game) = game_data {
-        if coordinates.0 == 0
-            || coordinates.1 == 0
-            || coordinates.0 > game.width
-            || coordinates.1 > game.height
-        {
-            msg.channel_id
-                .say(&ctx.http, "Coordinates out of bounds!")
-                .await
-                .ok();
-            return Ok(());
-        }
-
-        game.unflag((coordinates.0 - 1, coordinates.1 - 1));
-
-        send_game_render(ctx, msg.channel_id, &game).await.ok();
+    let planned = PlannedMove {
+        kind: MoveKind::Unflag,
+        coordinates: coordinates.unwrap(),
+    };
+    apply_moves(ctx, &Invocation::from(msg), &[planned], vec![]).await;
 
-        Ok(())
-    } else {
-        msg.channel_id.say(
-            &ctx.http,
-            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
-        )
-        .await
-        .ok();
-        return Ok(());
-    }
+    Ok(())
 }
 
 #[command]
 async fn stopgame(ctx: &Context, msg: &Message) -> CommandResult {
-    let author = &msg.author;
-
-    if author.bot {
+    if msg.author.bot {
         return Ok(());
     }
 
+    run_stopgame(ctx, &Invocation::from(msg)).await;
+
+    Ok(())
+}
+
+async fn run_stopgame(ctx: &Context, invocation: &Invocation) {
+    let author = &invocation.author;
+
     let data = ctx.data.read().await;
     let game_data = data.get::<GameDataKey>().unwrap();
 
-    if game_data.get_mut(&author.id.0).is_some() {
-        game_data.remove(&author.id.0);
-
-        msg.channel_id
-            .say(&ctx.http, "Successfuly ended game.")
-            .await
-            .ok();
+    let key = find_game_key(&data, author.id.0, invocation.channel_id);
 
-        return Ok(());
-    } else {
-        msg.channel_id.say(
+    if let Some(race) = key.as_ref().and_then(|key| find_race(&data, key)) {
+        finish_race(
             &ctx.http,
-            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+            invocation.channel_id,
+            &data,
+            author.id.0,
+            &race,
+            RaceFinish::Stopped,
         )
         .await
         .ok();
-        return Ok(());
+
+        return;
     }
+
+    let removed = key.map(|key| game_data.remove_if(&key, |_, board| board.can_stop(author.id.0)));
+
+    let reply = match removed {
+        Some(Some((_, board))) => {
+            finish_daily_attempt(&data, author.id.0, board.as_ref(), DailyOutcome::Forfeited);
+            "Successfuly ended game."
+        }
+        Some(None) => "You can't stop this game!",
+        None => "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+    };
+    invocation.channel_id.say(&ctx.http, reply).await.ok();
 }
 
-#[command]
-async fn resend(ctx: &Context, msg: &Message) -> CommandResult {
-    let author = &msg.author;
+/// The most games guildgames lists, so its reply stays under Discord's message length limit.
+const MAX_LISTED_GAMES: usize = 20;
 
-    if author.bot {
-        return Ok(());
-    }
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn guildgames(ctx: &Context, msg: &Message) -> CommandResult {
+    let channels = msg.guild_id.unwrap().channels(&ctx.http).await?;
 
     let data = ctx.data.read().await;
-    let game_data = data.get::<GameDataKey>().unwrap();
-    let game_data = game_data.get_mut(&author.id.0);
+    let now = DateTime::<Utc>::from(SystemTime::now());
+
+    let mut games = data
+        .get::<GameDataKey>()
+        .unwrap()
+        .iter()
+        .filter(|entry| channels.contains_key(&ChannelId(entry.key().channel())))
+        .map(|entry| {
+            let key = entry.key();
+            let board = entry.value();
+            let (width, height) = board.dimensions();
+
+            let owner = match key.owner() {
+                Some(player) => format!("<@{}>", player),
+                None => "Shared game".to_string(),
+            };
+            let name = key
+                .name()
+                .map(|name| format!(" ({})", name))
+                .unwrap_or_default();
+
+            (
+                board.time_started(),
+                format!(
+                    "{}{} in <#{}>: {} by {} with {} mines, started {} minutes ago, last move {} minutes ago",
+                    owner,
+                    name,
+                    key.channel(),
+                    width,
+                    height,
+                    board.number_of_mines(),
+                    (now - board.time_started()).num_minutes(),
+                    (now - board.last_activity()).num_minutes()
+                ),
+            )
+        })
+        .collect::<Vec<_>>();
+    games.sort_by_key(|(started, _)| *started);
 
-    if let Some(game) = game_data {
-        send_game_render(ctx, msg.channel_id, &game).await.ok();
-        return Ok(());
+    let reply = if games.is_empty() {
+        "There are no running games in this server.".to_string()
     } else {
-        msg.channel_id.say(
-            &ctx.http,
-            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
-        )
-        .await
-        .ok();
-        return Ok(());
-    }
-}
+        let mut lines = games
+            .iter()
+            .take(MAX_LISTED_GAMES)
+            .map(|(_, line)| line.clone())
+            .collect::<Vec<_>>();
+
+        if games.len() > MAX_LISTED_GAMES {
+            lines.push(format!("...and {} more", games.len() - MAX_LISTED_GAMES));
+        }
+        format!("Running games in this server:\n{}", lines.join("\n"))
+    };
 
-#[command]
-async fn help(ctx: &Context, msg: &Message) -> CommandResult {
     msg.channel_id
-        .say(
-            &ctx.http,
-            "Commands: startgame, stopgame, dig, flag, unflag, help, resend",
-        )
+        .send_message(&ctx.http, |m| {
+            m.content(reply)
+                .allowed_mentions(|mentions| mentions.empty_parse())
+        })
         .await
         .ok();
+
     Ok(())
 }
 
-fn process_coordinates(args: &Args) -> Option<(u32, u32)> {
-    let mut args = args.message().split(" ");
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn forcestop(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let here = args.message().trim().eq_ignore_ascii_case("here");
+
+    let target = match msg.mentions.first() {
+        Some(target) => Some(target.id.0),
+        None if here => None,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage:\nforcestop @user, to end every game that user has in this server\nforcestop here, to end the shared game in this channel",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
 
-    let mut positions = vec![];
+    let channels = msg.guild_id.unwrap().channels(&ctx.http).await?;
 
-    for _ in 0..2 {
-        if let Some(position) = args.next() {
-            if let Ok(position) = position.parse::<u32>() {
-                positions.push(position);
-            }
-        }
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let keys = match target {
+        Some(target) => game_data
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|key| {
+                key.owner() == Some(target) && channels.contains_key(&ChannelId(key.channel()))
+            })
+            .collect::<Vec<_>>(),
+        None => vec![GameKey::Channel(msg.channel_id.0)]
+            .into_iter()
+            .filter(|key| game_data.contains_key(key))
+            .collect(),
+    };
+
+    for key in &keys {
+        force_stop_game(&ctx.http, &data, key).await;
+    }
+
+    let reply = match keys.len() {
+        0 => "There was no game to stop.".to_string(),
+        1 => "Stopped 1 game.".to_string(),
+        count => format!("Stopped {} games.", count),
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+/// Ends a game the way its player stopping it would, without asking whether they can.
+async fn force_stop_game(http: &Http, data: &TypeMap, key: &GameKey) {
+    let channel = ChannelId(key.channel());
+
+    if let (Some(player), Some(race)) = (key.owner(), find_race(data, key)) {
+        finish_race(http, channel, data, player, &race, RaceFinish::Stopped)
+            .await
+            .ok();
+        return;
+    }
+
+    let removed = data.get::<GameDataKey>().unwrap().remove(key);
+
+    if let (Some(player), Some((_, board))) = (key.owner(), removed) {
+        finish_daily_attempt(data, player, board.as_ref(), DailyOutcome::Forfeited);
+    }
+}
+
+/// Daily results are shared by every server, so a server's moderators can only remove those of
+/// the server's own members.
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn purgestats(ctx: &Context, msg: &Message) -> CommandResult {
+    let target = match msg.mentions.first() {
+        Some(target) => target,
+        None => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: purgestats @user\nRemoves every daily challenge result of a member of this server.",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    if msg.guild_id.unwrap().member(ctx, target.id).await.is_err() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("{} isn't a member of this server.", target.name),
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let daily_results = data.get::<DailyDataKey>().unwrap();
+
+    let removed = daily_results.purge_user(target.id.0);
+    daily_results.save().ok();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Removed {} daily challenge results of {}.",
+                removed, target.name
+            ),
+        )
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn resend(ctx: &Context, msg: &Message) -> CommandResult {
+    if msg.author.bot {
+        return Ok(());
+    }
+
+    run_resend(ctx, &Invocation::from(msg)).await;
+
+    Ok(())
+}
+
+async fn run_resend(ctx: &Context, invocation: &Invocation) {
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = find_game_key(&data, invocation.author.id.0, invocation.channel_id)
+        .filter(|key| game_data.contains_key(key));
+
+    if let Some(key) = key {
+        board_message::show_board(
+            &ctx.http,
+            &data,
+            invocation.guild_id,
+            invocation.channel_id,
+            &key,
+            true,
+        )
+        .await
+        .ok();
+    } else {
+        invocation.channel_id.say(
+            &ctx.http,
+            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+        )
+        .await
+        .ok();
+    }
+}
+
+#[command]
+async fn games(ctx: &Context, msg: &Message) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let now = DateTime::<Utc>::from(SystemTime::now());
+
+    let mut games = data
+        .get::<GameDataKey>()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.key().owner() == Some(author.id.0))
+        .map(|entry| {
+            let board = entry.value();
+            let (width, height) = board.dimensions();
+            (
+                entry.key().clone(),
+                (width, height, board.number_of_mines()),
+                (now - board.last_activity()).num_minutes(),
+            )
+        })
+        .collect::<Vec<_>>();
+    games.sort_by(|a, b| (a.0.channel(), a.0.name()).cmp(&(b.0.channel(), b.0.name())));
+
+    if games.is_empty() {
+        msg.channel_id.say(
+            &ctx.http,
+            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+        )
+        .await
+        .ok();
+        return Ok(());
+    }
+
+    let lines = games
+        .iter()
+        .map(|(key, (width, height, mines), idle_minutes)| {
+            let current = find_game_key(&data, author.id.0, ChannelId(key.channel()))
+                .is_some_and(|current| current == *key);
+
+            format!(
+                "<#{}> {}: {} by {} with {} mines, last played {} minutes ago{}",
+                key.channel(),
+                key.name().unwrap_or("unnamed"),
+                width,
+                height,
+                mines,
+                idle_minutes,
+                if current { " (current)" } else { "" }
+            )
+        })
+        .collect::<Vec<_>>();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Your games:\n{}\nUse the command switchgame [name] to pick which game your moves in a channel go to, or switchgame shared for the channel's shared game.",
+                lines.join("\n")
+            ),
+        )
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn switchgame(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let name = args.message().trim().to_ascii_lowercase();
+    let name = Some(name.as_str()).filter(|name| !name.is_empty());
+
+    let key = match name {
+        Some(SHARED_GAME_NAME) => GameKey::Channel(msg.channel_id.0),
+        _ => GameKey::player(author.id.0, msg.channel_id.0, name),
+    };
+
+    let found = {
+        let data = ctx.data.read().await;
+        let found = data.get::<GameDataKey>().unwrap().contains_key(&key);
+
+        if found {
+            focus_game(&data, author.id.0, &key);
+        }
+        found
+    };
+
+    if !found {
+        let reply = match name {
+            Some(SHARED_GAME_NAME) => "There's no shared game in this channel!".to_string(),
+            Some(name) => format!("You don't have a game called {} in this channel! Use the command games to see your games.", name),
+            None => "You don't have an unnamed game in this channel! Use the command games to see your games.".to_string(),
+        };
+        msg.channel_id.say(&ctx.http, reply).await.ok();
+        return Ok(());
+    }
+
+    run_resend(ctx, &Invocation::from(msg)).await;
+
+    Ok(())
+}
+
+#[command]
+async fn watch(ctx: &Context, msg: &Message) -> CommandResult {
+    if msg.author.bot {
+        return Ok(());
+    }
+
+    let target = match msg.mentions.first() {
+        Some(target) if !target.bot => target,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: watch @user\nFollows that player's latest game in this server, mirrored in this channel. Use the command unwatch to stop.",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    // Only games in channels of this server the watcher can read can be watched, or in this
+    // channel outside of servers, so nobody can follow a game from somewhere they can't see.
+    let channels = match msg.guild_id {
+        Some(guild) => {
+            let partial_guild = guild.to_partial_guild(&ctx.http).await?;
+            let member = guild.member(ctx, msg.author.id).await?;
+
+            guild
+                .channels(&ctx.http)
+                .await?
+                .into_values()
+                .filter(|channel| {
+                    partial_guild
+                        .user_permissions_in(channel, &member)
+                        .is_ok_and(|permissions| permissions.read_messages())
+                })
+                .map(|channel| channel.id)
+                .collect()
+        }
+        None => vec![msg.channel_id],
+    };
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = game_data
+        .iter()
+        .filter(|entry| {
+            entry.key().owner() == Some(target.id.0)
+                && channels.contains(&ChannelId(entry.key().channel()))
+        })
+        .max_by_key(|entry| entry.value().last_activity())
+        .map(|entry| entry.key().clone());
+
+    let image = key.as_ref().and_then(|key| {
+        game_data
+            .get(key)
+            .map(|board| spectate::render_for_spectators(&**board))
+    });
+
+    if let (Some(key), Some(image)) = (key, image) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "Watching {}'s game. This board follows every move they make, and the mines stay hidden until the game is over.",
+                    target.name
+                ),
+            )
+            .await
+            .ok();
+        spectate::watch(&ctx.http, &data, &key, image, msg.channel_id)
+            .await
+            .ok();
+    } else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "{} doesn't have any running games in this server!",
+                    target.name
+                ),
+            )
+            .await
+            .ok();
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn unwatch(ctx: &Context, msg: &Message) -> CommandResult {
+    if msg.author.bot {
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+
+    let reply = if spectate::unwatch(&data, msg.channel_id) {
+        "Stopped following games in this channel."
+    } else {
+        "No games are being followed in this channel!"
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+async fn chord(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let coordinates = process_coordinates(&args);
+
+    if coordinates.is_none() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: chord X Y, or chord C7 with letter columns\nDigs every unflagged tile around a number that already has as many flags as mines.",
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    let planned = PlannedMove {
+        kind: MoveKind::Chord,
+        coordinates: coordinates.unwrap(),
+    };
+    apply_moves(ctx, &Invocation::from(msg), &[planned], vec![]).await;
+
+    Ok(())
+}
+
+#[command]
+async fn undo(ctx: &Context, msg: &Message) -> CommandResult {
+    if msg.author.bot {
+        return Ok(());
+    }
+
+    run_undo(ctx, &Invocation::from(msg)).await;
+
+    Ok(())
+}
+
+async fn run_undo(ctx: &Context, invocation: &Invocation) {
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = find_game_key(&data, invocation.author.id.0, invocation.channel_id);
+    let undone = key
+        .as_ref()
+        .and_then(|key| game_data.get_mut(key))
+        .map(|mut board| board.undo(invocation.author.id.0));
+
+    if let (Some(key), Some(undone)) = (key, undone) {
+        if let Err(e) = undone {
+            invocation
+                .channel_id
+                .say(&ctx.http, e.to_string())
+                .await
+                .ok();
+            return;
+        }
+
+        board_message::show_board(
+            &ctx.http,
+            &data,
+            invocation.guild_id,
+            invocation.channel_id,
+            &key,
+            false,
+        )
+        .await
+        .ok();
+        spectate::update_spectators(&ctx.http, &data, &key).await;
+    } else {
+        invocation.channel_id.say(
+            &ctx.http,
+            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+        )
+        .await
+        .ok();
+    }
+}
+
+#[command]
+async fn pause(ctx: &Context, msg: &Message) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = find_game_key(&data, author.id.0, msg.channel_id);
+    let paused = key
+        .as_ref()
+        .and_then(|key| game_data.get_mut(key))
+        .map(|mut board| board.pause(author.id.0));
+
+    if let (Some(key), Some(paused)) = (key, paused) {
+        if let Err(e) = paused {
+            msg.channel_id.say(&ctx.http, e.to_string()).await.ok();
+            return Ok(());
+        }
+
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Game paused! The board stays hidden until you use the command resume.",
+            )
+            .await
+            .ok();
+        board_message::show_board(&ctx.http, &data, msg.guild_id, msg.channel_id, &key, false)
+            .await
+            .ok();
+        spectate::update_spectators(&ctx.http, &data, &key).await;
+    } else {
+        msg.channel_id.say(
+            &ctx.http,
+            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+        )
+        .await
+        .ok();
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn autodig(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let auto_dig = match args.message().to_ascii_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: autodig on|off\nWhen it's on, every dig is followed by digging around any number that already has as many flags as mines. A wrong flag can make it hit a mine!",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data.read().await;
+    let user_settings = data.get::<SettingsDataKey>().unwrap();
+
+    user_settings.update(author.id.0, |settings| settings.auto_dig = auto_dig);
+    user_settings.save().ok();
+
+    let reply = if auto_dig {
+        "Auto-dig is on. Tiles around numbers with enough flags will be dug after each of your digs."
+    } else {
+        "Auto-dig is off."
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+async fn notation(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let letter_columns = match args.message().to_ascii_lowercase().as_str() {
+        "letters" => true,
+        "numbers" => false,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: notation letters|numbers\nWith letters, columns are labelled A, B, C and so on, and you can dig C7 instead of dig 3 7. Both ways of typing coordinates always work.",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data.read().await;
+    let user_settings = data.get::<SettingsDataKey>().unwrap();
+
+    user_settings.update(author.id.0, |settings| {
+        settings.letter_columns = letter_columns
+    });
+    user_settings.save().ok();
+
+    for mut entry in data.get::<GameDataKey>().unwrap().iter_mut() {
+        if entry.key().owner() == Some(author.id.0) {
+            entry.value_mut().set_letter_columns(letter_columns);
+        }
+    }
+
+    let reply = if letter_columns {
+        "Columns will be labelled with letters on your boards."
+    } else {
+        "Columns will be labelled with numbers on your boards."
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn boardmessages(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let repost_boards = match args.message().to_ascii_lowercase().as_str() {
+        "edit" => false,
+        "repost" => true,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: boardmessages edit|repost\nWith edit, moves update the last board message. With repost, every move posts a new one.",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data.read().await;
+    let guild_configs = data.get::<GuildConfigDataKey>().unwrap();
+
+    guild_configs.update(msg.guild_id.unwrap().0, |config| {
+        config.repost_boards = repost_boards
+    });
+    guild_configs.save().ok();
+
+    let reply = if repost_boards {
+        "Boards will be posted again after every move in this server."
+    } else {
+        "Boards will be edited in place after every move in this server."
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn prefix(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let new_prefix = match args.message().trim() {
+        "" => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Usage: prefix [new prefix]\nUse prefix reset to go back to {}",
+                        guilds::DEFAULT_PREFIX
+                    ),
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+        "reset" => None,
+        new_prefix if new_prefix.contains(char::is_whitespace) => {
+            msg.channel_id
+                .say(&ctx.http, "The prefix can't contain spaces!")
+                .await
+                .ok();
+            return Ok(());
+        }
+        new_prefix => Some(new_prefix.to_string()),
+    };
+
+    let data = ctx.data.read().await;
+    let guild_configs = data.get::<GuildConfigDataKey>().unwrap();
+
+    guild_configs.update(msg.guild_id.unwrap().0, |config| config.prefix = new_prefix);
+    guild_configs.save().ok();
+
+    let config = guild_configs.get(msg.guild_id.unwrap().0);
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("Commands in this server now start with {}", config.prefix()),
+        )
+        .await
+        .ok();
+
+    Ok(())
+}
+
+const BOTCHANNELS_USAGE: &str = "Usage: botchannels add|remove|clear [#channels]\nWithout channels, add and remove use this channel. While no channels are added, the bot answers in every channel.";
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn botchannels(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut words = args.message().split_whitespace();
+    let action = words.next().unwrap_or("").to_ascii_lowercase();
+
+    let channels = words
+        .map(serenity::utils::parse_channel)
+        .collect::<Option<Vec<_>>>();
+    let channels = match channels {
+        Some(channels) if channels.is_empty() => vec![msg.channel_id.0],
+        Some(channels) => channels,
+        None => {
+            msg.channel_id.say(&ctx.http, BOTCHANNELS_USAGE).await.ok();
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data.read().await;
+    let guild_configs = data.get::<GuildConfigDataKey>().unwrap();
+    let guild = msg.guild_id.unwrap();
+    let guild_id = guild.0;
+
+    // Channels from other servers are refused, except that a channel this server already allows
+    // can always be removed, even once it's been deleted.
+    let guild_channels = guild.channels(&ctx.http).await?;
+    let allowed_channels = guild_configs.get(guild_id).allowed_channels;
+    let removable = |channel: &u64| action == "remove" && allowed_channels.contains(channel);
+    let foreign = channels
+        .iter()
+        .find(|channel| !guild_channels.contains_key(&ChannelId(**channel)) && !removable(channel));
+
+    if let Some(channel) = foreign {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("<#{}> isn't a channel in this server.", channel),
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    match action.as_str() {
+        "add" => guild_configs.update(guild_id, |config| {
+            for channel in channels {
+                if !config.allowed_channels.contains(&channel) {
+                    config.allowed_channels.push(channel);
+                }
+            }
+        }),
+        "remove" => guild_configs.update(guild_id, |config| {
+            config
+                .allowed_channels
+                .retain(|channel| !channels.contains(channel))
+        }),
+        "clear" => guild_configs.update(guild_id, |config| config.allowed_channels.clear()),
+        _ => {
+            msg.channel_id.say(&ctx.http, BOTCHANNELS_USAGE).await.ok();
+            return Ok(());
+        }
+    }
+    guild_configs.save().ok();
+
+    let allowed_channels = guild_configs.get(guild_id).allowed_channels;
+    let reply = if allowed_channels.is_empty() {
+        "The bot now answers in every channel.".to_string()
+    } else {
+        format!(
+            "The bot now only answers in {}",
+            allowed_channels
+                .iter()
+                .map(|channel| format!("<#{}>", channel))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    };
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn preset(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let args = args.message().to_ascii_lowercase();
+    let words = args.split_whitespace().collect::<Vec<_>>();
+
+    let data = ctx.data.read().await;
+    let guild_configs = data.get::<GuildConfigDataKey>().unwrap();
+    let guild_id = msg.guild_id.unwrap().0;
+
+    let reply = match words.as_slice() {
+        ["add", name, width, height, mines] => {
+            let preset = match (width.parse(), height.parse(), mines.parse()) {
+                (Ok(width), Ok(height), Ok(mines)) => Preset {
+                    width,
+                    height,
+                    mines,
+                },
+                _ => {
+                    msg.channel_id
+                        .say(&ctx.http, "The width, height and mines must be numbers!")
+                        .await
+                        .ok();
+                    return Ok(());
+                }
+            };
+
+            if let Err(e) = preset.check(name) {
+                e.to_string()
+            } else {
+                guild_configs.update(guild_id, |config| {
+                    config.presets.insert(name.to_string(), preset);
+                });
+                format!(
+                    "Added the preset {}: {} by {} with {} mines.",
+                    name, preset.width, preset.height, preset.mines
+                )
+            }
+        }
+        ["remove", name] => {
+            let mut removed = false;
+            guild_configs.update(guild_id, |config| {
+                removed = config.presets.remove(*name).is_some()
+            });
+
+            if removed {
+                format!("Removed the preset {}.", name)
+            } else {
+                format!("There is no preset called {}!", name)
+            }
+        }
+        _ => "Usage:\npreset add [name] [width] [height] [mines]\npreset remove [name]\nPresets can be used anywhere a difficulty can.".to_string(),
+    };
+    guild_configs.save().ok();
+
+    msg.channel_id.say(&ctx.http, reply).await.ok();
+
+    Ok(())
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn limits(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let words = args.message().split_whitespace().collect::<Vec<_>>();
+
+    let parse_limit = |word: &str| match word {
+        "none" => Some(None),
+        word => word
+            .parse::<u32>()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .map(Some),
+    };
+
+    let new_limits = match words.as_slice() {
+        ["none"] => Some(guilds::Limits::default()),
+        [width, height, mines] => {
+            match (parse_limit(width), parse_limit(height), parse_limit(mines)) {
+                (Some(max_width), Some(max_height), Some(max_mines)) => Some(guilds::Limits {
+                    max_width,
+                    max_height,
+                    max_mines,
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    let new_limits = match new_limits {
+        Some(new_limits) => new_limits,
+        None => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    "Usage: limits [max width] [max height] [max mines]\nUse none for any of them to leave it unlimited, or limits none to remove every limit.",
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data.read().await;
+    let guild_configs = data.get::<GuildConfigDataKey>().unwrap();
+
+    guild_configs.update(msg.guild_id.unwrap().0, |config| config.limits = new_limits);
+    guild_configs.save().ok();
+
+    msg.channel_id
+        .say(&ctx.http, "Updated the board limits for this server.")
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn resume(ctx: &Context, msg: &Message) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = find_game_key(&data, author.id.0, msg.channel_id);
+    let resumed = key
+        .as_ref()
+        .and_then(|key| game_data.get_mut(key))
+        .map(|mut board| board.resume(author.id.0));
+
+    if let (Some(key), Some(resumed)) = (key, resumed) {
+        if let Err(e) = resumed {
+            msg.channel_id.say(&ctx.http, e.to_string()).await.ok();
+            return Ok(());
+        }
+
+        board_message::show_board(&ctx.http, &data, msg.guild_id, msg.channel_id, &key, false)
+            .await
+            .ok();
+        spectate::update_spectators(&ctx.http, &data, &key).await;
+    } else {
+        msg.channel_id.say(
+            &ctx.http,
+            "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+        )
+        .await
+        .ok();
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn spoilerboard(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let guild_config = guild_config(ctx, msg.guild_id).await;
+
+    let game_settings = match guild_config.difficulty_settings(&args.message().to_ascii_lowercase())
+    {
+        Some(settings) if args.len() == 1 => settings,
+        _ => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Usage: spoilerboard [difficulty]\nDifficulties: {}",
+                        guild_config.difficulty_names().join(", ")
+                    ),
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    if let Err(e) = guild_config.limits.check(game_settings) {
+        msg.channel_id.say(&ctx.http, e.to_string()).await.ok();
+        return Ok(());
+    }
+
+    let game = match Game::new_generated(game_settings.0, game_settings.1, game_settings.2) {
+        Ok(game) => game,
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Couldn't generate the board: {}", e))
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    let messages = match spoiler::render_spoiler_board(&game) {
+        Ok(messages) => messages,
+        Err(e) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("This board can't be sent as spoilers: {}", e),
+                )
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Spoiler board: {} by {} with {} mines",
+                game.width, game.height, game.number_of_mines
+            ),
+        )
+        .await
+        .ok();
+
+    for message in messages {
+        msg.channel_id.say(&ctx.http, message).await.ok();
+    }
+
+    Ok(())
+}
+
+#[command]
+async fn daily(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    match args.message().to_ascii_lowercase().as_str() {
+        "" => (),
+        "results" => {
+            send_daily_results(ctx, msg.channel_id).await.ok();
+            return Ok(());
+        }
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage:\ndaily\ndaily results")
+                .await
+                .ok();
+            return Ok(());
+        }
+    }
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let daily_results = data.get::<DailyDataKey>().unwrap();
+    let user_settings = data.get::<SettingsDataKey>().unwrap();
+
+    let key = GameKey::player(author.id.0, msg.channel_id.0, Some(daily::DAILY_GAME_NAME));
+
+    // A daily game still running from before midnight would otherwise be replaced while its
+    // attempt is still in progress.
+    if game_data.contains_key(&key) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "You're still playing a daily challenge in this channel!\nUse the command switchgame daily to finish it, or the command stopgame to give up on it.",
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    let date = daily::today();
+
+    let game_settings = game::difficulty_settings(daily::DAILY_DIFFICULTY).unwrap();
+    let mut game = Game::new(game_settings.0, game_settings.1, game_settings.2);
+    if let Err(e) = game.start_seeded(daily::daily_seed(date)) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("Today's daily challenge can't be played: {}", e),
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+    game.daily_date = Some(date);
+    // Rating can take seconds, so it runs on a blocking thread like first digs do.
+    let mut game = tokio::task::spawn_blocking(move || {
+        game.rating = solver::rate(&game);
+        game
+    })
+    .await?;
+    game.channel_id = Some(msg.channel_id.0);
+    game.letter_columns = user_settings.get(author.id.0).letter_columns;
+    game.allow_undo = false;
+    game.subscribe(Arc::new(DailyAttemptListener {
+        results: Arc::clone(daily_results),
+        user_id: author.id.0,
+    }));
+
+    if !daily_results.start_attempt(date, author.id.0, &author.name) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "You've already played today's daily challenge! Use the command daily results to see the leaderboard.",
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+    daily_results.save().ok();
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "Daily challenge for {}! Everyone gets this board and this opening, and you only get one try.",
+                date
+            ),
+        )
+        .await
+        .ok();
+
+    focus_game(&data, author.id.0, &key);
+    game_data.insert(key.clone(), Box::new(game));
+
+    board_message::show_board(&ctx.http, &data, msg.guild_id, msg.channel_id, &key, true)
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn challenge(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let guild_config = guild_config(ctx, msg.guild_id).await;
+    let game_settings = args
+        .message()
+        .split_whitespace()
+        .last()
+        .and_then(|difficulty| guild_config.difficulty_settings(&difficulty.to_ascii_lowercase()));
+
+    let (opponent, game_settings) = match (msg.mentions.first(), game_settings) {
+        (Some(opponent), Some(game_settings)) if args.len() == 2 => (opponent, game_settings),
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: challenge @user [difficulty]")
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    if opponent.bot || opponent.id == author.id {
+        msg.channel_id
+            .say(&ctx.http, "You can't challenge that user!")
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    if let Err(e) = guild_config.limits.check(game_settings) {
+        msg.channel_id.say(&ctx.http, e.to_string()).await.ok();
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let race_data = data.get::<RaceDataKey>().unwrap();
+
+    if race_data.contains_key(&author.id.0) || race_data.contains_key(&opponent.id.0) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Both players need to finish their current races before a new one can start.",
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    let now = DateTime::<Utc>::from(SystemTime::now());
+    let challenge_data = data.get::<ChallengeDataKey>().unwrap();
+    challenge_data.retain(|_, challenge| !challenge.is_expired(now));
+
+    // A player only has one challenge waiting at a time, so a newer one can't quietly replace
+    // the one they were about to accept.
+    let issued = match challenge_data.entry(opponent.id.0) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(Challenge {
+                challenger: author.id.0,
+                challenger_name: author.name.clone(),
+                game_settings,
+                issued: now,
+            });
+            true
+        }
+    };
+
+    if !issued {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "{} already has a challenge waiting! Challenges expire if they aren't accepted within {} minutes.",
+                    opponent.name,
+                    race::CHALLENGE_MINUTES
+                ),
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "{} challenged {} to a race! Use the command accept within {} minutes to start it.",
+                author.name,
+                opponent.name,
+                race::CHALLENGE_MINUTES
+            ),
+        )
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn accept(ctx: &Context, msg: &Message) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let race_data = data.get::<RaceDataKey>().unwrap();
+
+    let now = DateTime::<Utc>::from(SystemTime::now());
+    let challenge = data
+        .get::<ChallengeDataKey>()
+        .unwrap()
+        .remove(&author.id.0)
+        .filter(|(_, challenge)| !challenge.is_expired(now));
+
+    let challenge = match challenge {
+        Some((_, challenge)) => challenge,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Nobody has challenged you to a race!")
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    if race_data.contains_key(&author.id.0) || race_data.contains_key(&challenge.challenger) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Both players need to finish their current races before a new one can start.",
+            )
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    // Races are played with the default safety policy, which the mines have to fit around.
+    let (width, height, number_of_mines) = challenge.game_settings;
+    let mut game = Game::new(width, height, number_of_mines);
+    if let Err(e) = game.validate() {
+        msg.channel_id
+            .say(&ctx.http, format!("That race can't be played: {}", e))
+            .await
+            .ok();
+        return Ok(());
+    }
+
+    let layout = Arc::new(Mutex::new(None));
+
+    race_data.insert(
+        author.id.0,
+        RaceEntry {
+            name: author.name.clone(),
+            opponent: challenge.challenger,
+            opponent_name: challenge.challenger_name.clone(),
+            channel: msg.channel_id.0,
+            layout: Arc::clone(&layout),
+        },
+    );
+    race_data.insert(
+        challenge.challenger,
+        RaceEntry {
+            name: challenge.challenger_name.clone(),
+            opponent: author.id.0,
+            opponent_name: author.name.clone(),
+            channel: msg.channel_id.0,
+            layout,
+        },
+    );
+
+    let user_settings = data.get::<SettingsDataKey>().unwrap();
+
+    game.channel_id = Some(msg.channel_id.0);
+    game.letter_columns = user_settings.get(author.id.0).letter_columns;
+    let mut challenger_game = Game::new(width, height, number_of_mines);
+    challenger_game.channel_id = Some(msg.channel_id.0);
+    challenger_game.letter_columns = user_settings.get(challenge.challenger).letter_columns;
+    game.allow_undo = false;
+    challenger_game.allow_undo = false;
+
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!(
+                "The race between {} and {} has started! You both get the same mines, and the first dig clears the same opening on both boards.",
+                challenge.challenger_name, author.name
+            ),
+        )
+        .await
+        .ok();
+
+    for (player, game) in [(author.id.0, game), (challenge.challenger, challenger_game)] {
+        let key = GameKey::player(player, msg.channel_id.0, Some(race::RACE_GAME_NAME));
+
+        focus_game(&data, player, &key);
+        game_data.insert(key, Box::new(game));
+    }
+
+    let key = GameKey::player(author.id.0, msg.channel_id.0, Some(race::RACE_GAME_NAME));
+    board_message::show_board(&ctx.http, &data, msg.guild_id, msg.channel_id, &key, true)
+        .await
+        .ok();
+
+    Ok(())
+}
+
+#[command]
+async fn flagstorm(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author = &msg.author;
+
+    if author.bot {
+        return Ok(());
+    }
+
+    let guild_config = guild_config(ctx, msg.guild_id).await;
+    let game_settings = args
+        .message()
+        .split_whitespace()
+        .last()
+        .and_then(|difficulty| guild_config.difficulty_settings(&difficulty.to_ascii_lowercase()));
+
+    let game_settings = match game_settings {
+        Some(game_settings) if args.len() == 2 => game_settings,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: flagstorm @user [difficulty]")
+                .await
+                .ok();
+            return Ok(());
+        }
+    };
+
+    start_game(
+        ctx,
+        &Invocation::from(msg),
+        board::find_variant("flagstorm").unwrap(),
+        GameSetup {
+            game_settings,
+            safety_policy: SafetyPolicy::default(),
+            requested_tier: None,
+            players: vec![],
+        },
+        None,
+    )
+    .await;
+
+    Ok(())
+}
+
+#[command]
+async fn help(ctx: &Context, msg: &Message) -> CommandResult {
+    msg.channel_id
+        .say(
+            &ctx.http,
+            "Commands: startgame, joingame, stopgame, games, switchgame, watch, unwatch, guildgames, forcestop, purgestats, dig, play, chord, undo, flag, unflag, pause, resume, autodig, notation, boardmessages, prefix, botchannels, preset, limits, help, resend, spoilerboard, daily, challenge, accept, flagstorm\nstartgame, dig, flag, unflag, resend and stopgame are also available as slash commands.",
+        )
+        .await
+        .ok();
+    Ok(())
+}
+
+/// Records how a daily game ended when it ends without a win or a loss. Does nothing for other
+/// games.
+fn finish_daily_attempt(data: &TypeMap, user_id: u64, board: &dyn Board, outcome: DailyOutcome) {
+    let date = match board.game().and_then(|game| game.daily_date) {
+        Some(date) => date,
+        None => return,
+    };
+    let daily_results = data.get::<DailyDataKey>().unwrap();
+
+    if daily_results.finish_attempt(date, user_id, outcome) {
+        daily_results.save().ok();
+    }
+}
+
+async fn send_daily_results(ctx: &Context, channel: ChannelId) -> Result<Message, serenity::Error> {
+    let data = ctx.data.read().await;
+    let daily_results = data.get::<DailyDataKey>().unwrap();
+
+    let date = daily::today();
+    let entries = daily_results.entries(date);
+    let leaderboard = daily_results.leaderboard(date);
+
+    channel
+        .send_message(&ctx.http, |m| {
+            m.add_embed(|embed| {
+                embed.title(format!("Daily Challenge Results for {}", date));
+
+                if leaderboard.is_empty() {
+                    embed.description("Nobody has cleared today's board yet.");
+                } else {
+                    embed.description(
+                        leaderboard
+                            .iter()
+                            .take(10)
+                            .enumerate()
+                            .map(|(place, (entry, milliseconds))| {
+                                format!(
+                                    "{}. {} - {}:{:02}.{:03}{}",
+                                    place + 1,
+                                    entry.user_name,
+                                    milliseconds / 60000,
+                                    milliseconds / 1000 % 60,
+                                    milliseconds % 1000,
+                                    if entry.paused { " (paused)" } else { "" }
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                    );
+                }
+
+                embed.field("Attempts", entries.len(), true);
+                embed.field("Cleared", leaderboard.len(), true);
+                embed
+            });
+            m
+        })
+        .await
+}
+
+/// Digs on a racing player's board. The first dig of the race decides the mines for both
+/// players; returns true if this dig was it.
+fn race_dig(
+    board: &mut dyn Board,
+    race: &RaceEntry,
+    position: (u32, u32),
+) -> Result<bool, MoveError> {
+    let game = board.game_mut().ok_or(MoveError::Unsupported)?;
+    if game.state != GameState::NotStarted {
+        game.dig(position)?;
+        return Ok(false);
+    }
+
+    game.check_position(position)?;
+    let mut layout = race.layout.lock().unwrap();
+
+    if let Some(layout) = &*layout {
+        game.start_with_layout(&layout.tiles, layout.first_dig);
+        game.rating = layout.rating;
+
+        // A first dig inside the copied opening has nothing left to reveal.
+        if !game.tiles[position.1 as usize][position.0 as usize].is_revealed {
+            game.dig(position)?;
+        }
+        return Ok(false);
+    }
+
+    game.dig(position)?;
+    *layout = Some(RaceLayout {
+        tiles: game.tiles.clone(),
+        first_dig: position,
+        rating: game.rating,
+    });
+    Ok(true)
+}
+
+/// The race a game belongs to, if it's one of the two boards of a race.
+fn find_race(data: &TypeMap, key: &GameKey) -> Option<RaceEntry> {
+    let player = key.owner()?;
+
+    data.get::<RaceDataKey>()
+        .unwrap()
+        .get(&player)
+        .map(|race| race.clone())
+        .filter(|race| race.game_key(player) == *key)
+}
+
+fn apply_race_layout(game: &mut Game, race: &RaceEntry) {
+    if let Some(layout) = &*race.layout.lock().unwrap() {
+        game.start_with_layout(&layout.tiles, layout.first_dig);
+        game.rating = layout.rating;
+    }
+}
+
+async fn finish_race(
+    http: &Http,
+    channel: ChannelId,
+    data: &TypeMap,
+    player: u64,
+    race: &RaceEntry,
+    finish: RaceFinish,
+) -> Result<Message, serenity::Error> {
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let race_data = data.get::<RaceDataKey>().unwrap();
+
+    let player_board = game_data
+        .remove(&race.game_key(player))
+        .map(|(_, board)| board);
+    let opponent_board = game_data
+        .remove(&race.game_key(race.opponent))
+        .map(|(_, board)| board);
+    race_data.remove(&player);
+    race_data.remove(&race.opponent);
+
+    let opponent_status = if let RaceFinish::Cleared = finish {
+        "Still playing"
+    } else {
+        "Wins by forfeit"
+    };
+
+    let race_result = |board: Option<Box<dyn Board>>, status: &str| match board {
+        Some(board) if board.state() != GameState::NotStarted => match board.game() {
+            Some(game) => {
+                let difference = game.active_time();
+                format!(
+                    "{}\nTime: {}:{:02}\nMoves: {}",
+                    status,
+                    difference.num_minutes(),
+                    difference.num_seconds() - difference.num_minutes() * 60,
+                    game.move_count
+                )
+            }
+            None => status.to_string(),
+        },
+        _ => format!("{}\nTime: 0:00\nMoves: 0", status),
+    };
+
+    let player_result = race_result(player_board, finish.status());
+    let opponent_result = race_result(opponent_board, opponent_status);
+
+    channel
+        .send_message(http, |m| {
+            m.add_embed(|embed| {
+                embed.title("Race Summary");
+                embed.description(finish.description(&race.name, &race.opponent_name));
+                embed.field(&race.name, player_result, true);
+                embed.field(&race.opponent_name, opponent_result, true);
+                embed
+            });
+            m
+        })
+        .await
+}
+
+/// The game a player's moves go to: the game they last started, joined or switched to in the
+/// channel, or else their unnamed game there. Without one, it's the shared game in the channel.
+/// Only direct lookups are made, since this runs for every move.
+fn find_game_key(data: &TypeMap, player: u64, channel: ChannelId) -> Option<GameKey> {
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let focused = data
+        .get::<FocusDataKey>()
+        .unwrap()
+        .get(&(player, channel.0))
+        .map(|key| key.clone());
+
+    focused
+        .into_iter()
+        .chain([
+            GameKey::player(player, channel.0, None),
+            GameKey::Channel(channel.0),
+        ])
+        .find(|key| game_data.contains_key(key))
+}
+
+async fn start_game(
+    ctx: &Context,
+    invocation: &Invocation,
+    variant: &Variant,
+    mut setup: GameSetup,
+    name: Option<&str>,
+) {
+    let author = &invocation.author;
+
+    setup.players = vec![(author.id.0, author.name.clone())];
+    setup.players.extend(
+        invocation
+            .mentions
+            .iter()
+            .filter(|user| user.id != author.id)
+            .map(|user| (user.id.0, user.name.clone())),
+    );
+
+    if setup.players.len() != variant.players {
+        invocation
+            .channel_id
+            .say(&ctx.http, format!("Usage: {}", variant.usage))
+            .await
+            .ok();
+        return;
+    }
+
+    if invocation.mentions.iter().any(|user| user.bot) {
+        invocation
+            .channel_id
+            .say(&ctx.http, "You can't play against that user!")
+            .await
+            .ok();
+        return;
+    }
+
+    let limits = guild_config(ctx, invocation.guild_id).await.limits;
+    if let Err(e) = limits.check(setup.game_settings) {
+        invocation
+            .channel_id
+            .say(&ctx.http, e.to_string())
+            .await
+            .ok();
+        return;
+    }
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+
+    let key = if variant.shared {
+        GameKey::Channel(invocation.channel_id.0)
+    } else {
+        GameKey::player(author.id.0, invocation.channel_id.0, name)
+    };
+
+    if game_data.contains_key(&key) {
+        let reply = match key {
+            GameKey::Player { name: None, .. } => "You already have a running game in this channel!\nUse the command stopgame to end your current game if you would like to end it.\nUse the command resend if you would like to see your current progress.\nAdd name=[name] to startgame to play another game next to it.",
+            GameKey::Player { .. } => "You already have a game with that name in this channel!\nUse the command switchgame [name] to play it.",
+            GameKey::Channel(_) => "This channel already has a running game!\nUse the command resend to see it.",
+        };
+        invocation.channel_id.say(&ctx.http, reply).await.ok();
+        return;
+    }
+
+    let mut board = match (variant.create)(&setup) {
+        Ok(board) => board,
+        Err(e) => {
+            invocation
+                .channel_id
+                .say(&ctx.http, format!("That game can't be played: {}", e))
+                .await
+                .ok();
+            return;
+        }
+    };
+
+    if let Some(game) = board.game_mut() {
+        game.channel_id = Some(invocation.channel_id.0);
+    }
+    board.set_letter_columns(
+        data.get::<SettingsDataKey>()
+            .unwrap()
+            .get(author.id.0)
+            .letter_columns,
+    );
+
+    if let Some(introduction) = board.introduction() {
+        invocation
+            .channel_id
+            .say(&ctx.http, introduction)
+            .await
+            .ok();
+    }
+
+    for (player, _) in &setup.players {
+        focus_game(&data, *player, &key);
+    }
+    game_data.insert(key.clone(), board);
+
+    board_message::show_board(
+        &ctx.http,
+        &data,
+        invocation.guild_id,
+        invocation.channel_id,
+        &key,
+        true,
+    )
+    .await
+    .ok();
+}
+
+/// Plays a list of moves on the author's game in order, then sends one render. A dig that ends
+/// the game stops the list, and every move that couldn't be played is listed after the render
+/// along with anything in `report`. A single move that fails is only answered with its error.
+async fn apply_moves(
+    ctx: &Context,
+    invocation: &Invocation,
+    moves: &[PlannedMove],
+    mut report: Vec<String>,
+) {
+    let author = &invocation.author;
+
+    let data = ctx.data.read().await;
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let settings = data.get::<SettingsDataKey>().unwrap().get(author.id.0);
+
+    let key = match find_game_key(&data, author.id.0, invocation.channel_id) {
+        Some(key) => key,
+        None => {
+            invocation.channel_id.say(
+                &ctx.http,
+                "You don't have any running games! Use the command startgame [difficulty] to start a game.",
+            )
+            .await
+            .ok();
+            return;
+        }
+    };
+
+    if let Some(first) = moves
+        .first()
+        .filter(|planned| planned.kind == MoveKind::Dig)
+    {
+        prepare_first_dig(game_data, &key, first.coordinates).await;
+    }
+
+    let board = game_data.get_mut(&key);
+
+    let mut board = match board {
+        Some(board) => board,
+        None => return,
+    };
+
+    let race = find_race(&data, &key);
+
+    let single = moves.len() == 1;
+    let mut played = 0;
+    let mut cleared_opening = false;
+    let mut announcement = None;
+
+    for planned in moves {
+        if board.is_finished() {
+            report.push(format!(
+                "`{}`: skipped, the game is over",
+                planned.describe(board.letter_columns())
+            ));
+            continue;
+        }
+
+        let result = if coordinates_in_bounds(board.dimensions(), planned.coordinates) {
+            let position = (planned.coordinates.0 - 1, planned.coordinates.1 - 1);
+
+            match (planned.kind, &race) {
+                (MoveKind::Dig, Some(race)) => {
+                    race_dig(board.as_mut(), race, position).map(|opened| (opened, None))
+                }
+                (MoveKind::Dig, None) => board
+                    .dig(author.id.0, position)
+                    .map(|outcome| (false, board.announcement(&outcome))),
+                (MoveKind::Flag, _) => board.flag(author.id.0, position).map(|_| (false, None)),
+                (MoveKind::Unflag, _) => board.unflag(author.id.0, position).map(|_| (false, None)),
+                (MoveKind::Chord, _) => board
+                    .chord(author.id.0, position)
+                    .map(|outcome| (false, board.announcement(&outcome))),
+            }
+        } else {
+            Err(MoveError::OutOfBounds)
+        };
+
+        let dug = result.is_ok() && matches!(planned.kind, MoveKind::Dig | MoveKind::Chord);
+
+        match result {
+            Ok((opened, move_announcement)) => {
+                played += 1;
+                cleared_opening |= opened;
+                announcement = move_announcement.or(announcement);
+            }
+            Err(e) if single => report.push(e.to_string()),
+            Err(e) => report.push(format!(
+                "`{}`: {}",
+                planned.describe(board.letter_columns()),
+                e
+            )),
+        }
+
+        if dug && race.is_none() && settings.auto_dig && !board.is_finished() {
+            let auto_dug = board.dig_satisfied_tiles(author.id.0);
+
+            if !auto_dug.is_empty() && board.state() == GameState::Lost {
+                report.push("Auto-dig trusted a wrong flag and hit a mine!".to_string());
+            }
+        }
+    }
+
+    let finished = board.is_finished();
+    let won = board.state() == GameState::Won;
+    let summary = (finished && race.is_none()).then(|| board.summary());
+    drop(board);
+
+    if played == 0 {
+        invocation
+            .channel_id
+            .say(&ctx.http, report.join("\n"))
+            .await
+            .ok();
+        return;
+    }
+
+    board_message::show_board(
+        &ctx.http,
+        &data,
+        invocation.guild_id,
+        invocation.channel_id,
+        &key,
+        false,
+    )
+    .await
+    .ok();
+    spectate::update_spectators(&ctx.http, &data, &key).await;
+
+    if !report.is_empty() {
+        invocation
+            .channel_id
+            .say(&ctx.http, report.join("\n"))
+            .await
+            .ok();
+    }
+
+    if let Some(race) = race {
+        if finished {
+            let finish = if won {
+                RaceFinish::Cleared
+            } else {
+                RaceFinish::Exploded
+            };
+
+            finish_race(
+                &ctx.http,
+                invocation.channel_id,
+                &data,
+                author.id.0,
+                &race,
+                finish,
+            )
+            .await
+            .ok();
+        } else if cleared_opening {
+            let opponent_key = race.game_key(race.opponent);
+            let applied = game_data
+                .get_mut(&opponent_key)
+                .map(|mut opponent_board| {
+                    if let Some(game) = opponent_board.game_mut() {
+                        apply_race_layout(game, &race);
+                    }
+                })
+                .is_some();
+
+            if applied {
+                invocation
+                    .channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "The same opening was cleared on {}'s board:",
+                            race.opponent_name
+                        ),
+                    )
+                    .await
+                    .ok();
+                board_message::show_board(
+                    &ctx.http,
+                    &data,
+                    invocation.guild_id,
+                    invocation.channel_id,
+                    &opponent_key,
+                    false,
+                )
+                .await
+                .ok();
+                spectate::update_spectators(&ctx.http, &data, &opponent_key).await;
+            }
+        }
+        return;
+    }
+
+    if let Some(summary) = summary {
+        send_game_summary(ctx, invocation.channel_id, summary)
+            .await
+            .ok();
+
+        game_data.remove(&key);
+    } else if let Some(announcement) = announcement {
+        invocation
+            .channel_id
+            .say(&ctx.http, announcement)
+            .await
+            .ok();
+    }
+}
+
+/// Generates the layout for a game's first dig on a blocking thread, since rating it can take
+/// seconds on big boards. The game isn't held while that runs, so other moves aren't held up.
+async fn prepare_first_dig(
+    game_data: &DashMap<GameKey, Box<dyn Board>>,
+    key: &GameKey,
+    coordinates: (u32, u32),
+) {
+    let blank = game_data
+        .get(key)
+        .filter(|board| {
+            board.state() == GameState::NotStarted
+                && coordinates_in_bounds(board.dimensions(), coordinates)
+        })
+        .and_then(|board| board.game().map(Game::blank_copy));
+
+    let blank = match blank {
+        Some(blank) => blank,
+        None => return,
+    };
+
+    let position = (coordinates.0 - 1, coordinates.1 - 1);
+    let layout = tokio::task::spawn_blocking(move || blank.generate_layout(position)).await;
+
+    if let (Ok(Ok(layout)), Some(mut board)) = (layout, game_data.get_mut(key)) {
+        if let Some(game) = board.game_mut() {
+            game.prepare_layout(layout);
+        }
+    }
+}
+
+/// Parses a difficulty or server preset followed by an optional safety policy and difficulty
+/// rating, in any order. The players are left for the caller to fill in.
+fn parse_game_options(args: &[&str], guild_config: &GuildConfig) -> Option<GameSetup> {
+    let (difficulty, options) = args.split_first()?;
+
+    let game_settings = guild_config.difficulty_settings(difficulty)?;
+    let mut safety_policy = None;
+    let mut requested_tier = None;
+
+    for option in options {
+        if let Some(policy) = SafetyPolicy::parse(option).filter(|_| safety_policy.is_none()) {
+            safety_policy = Some(policy);
+        } else if let Some(tier) = Tier::parse(option).filter(|_| requested_tier.is_none()) {
+            requested_tier = Some(tier);
+        } else {
+            return None;
+        }
+    }
+
+    Some(GameSetup {
+        game_settings,
+        safety_policy: safety_policy.unwrap_or_default(),
+        requested_tier,
+        players: vec![],
+    })
+}
+
+fn coordinates_in_bounds(dimensions: (u32, u32), coordinates: (u32, u32)) -> bool {
+    coordinates.0 != 0
+        && coordinates.1 != 0
+        && coordinates.0 <= dimensions.0
+        && coordinates.1 <= dimensions.1
+}
+
+/// Reads coordinates written as X Y, or in letter notation such as C7.
+fn process_coordinates(args: &Args) -> Option<(u32, u32)> {
+    if let Some(coordinates) = moves::parse_coordinates(args.message().trim()) {
+        return Some(coordinates);
+    }
+
+    let mut args = args.message().split(" ");
+
+    let mut positions = vec![];
+
+    for _ in 0..2 {
+        if let Some(position) = args.next() {
+            if let Ok(position) = position.parse::<u32>() {
+                positions.push(position);
+            }
+        }
     }
 
-    if let Some(_) = args.next() {
+    if args.next().is_some() {
         return None;
     }
 
@@ -392,19 +2603,42 @@ fn process_coordinates(args: &Args) -> Option<(u32, u32)> {
     }
 }
 
-async fn send_game_render(
+async fn send_game_summary(
     ctx: &Context,
     channel: ChannelId,
-    game: &Game,
+    summary: Summary,
 ) -> Result<Message, serenity::Error> {
-    let map = DefaultMinesweeperDrawer::draw_board(game);
+    channel
+        .send_message(&ctx.http, |m| {
+            m.add_embed(|embed| {
+                embed.title(summary.title);
+                embed.description(summary.description);
+                for (name, value, inline) in summary.fields {
+                    embed.field(name, value, inline);
+                }
+                embed
+            });
+            m
+        })
+        .await
+}
 
+async fn send_game_render(
+    http: &Http,
+    channel: ChannelId,
+    render: BoardRender,
+) -> Result<Message, serenity::Error> {
     let attachment = AttachmentType::Bytes {
-        data: Cow::Owned(map.encode_png().unwrap()),
+        data: Cow::Owned(render.image),
         filename: "File.png".to_string(),
     };
 
     channel
-        .send_message(&ctx.http, |m| m.add_file(attachment))
+        .send_message(http, |m| {
+            if let Some(components) = render.components {
+                m.set_components(components);
+            }
+            m.add_file(attachment)
+        })
         .await
 }
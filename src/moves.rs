@@ -0,0 +1,183 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveKind {
+    Dig,
+    Flag,
+    Unflag,
+    Chord,
+}
+
+/// A move asked for by dig, flag, unflag or play. Coordinates are one-based, as players type
+/// them, and haven't been checked against the board yet.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PlannedMove {
+    pub kind: MoveKind,
+    pub coordinates: (u32, u32),
+}
+
+impl PlannedMove {
+    /// Parses one move of a move list: d, f, u or c to dig, flag, unflag or chord, followed by
+    /// coordinates such as 3,4 or C4.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut chars = text.chars();
+
+        let kind = match chars.next()?.to_ascii_lowercase() {
+            'd' => MoveKind::Dig,
+            'f' => MoveKind::Flag,
+            'u' => MoveKind::Unflag,
+            'c' => MoveKind::Chord,
+            _ => return None,
+        };
+
+        Some(PlannedMove {
+            kind,
+            coordinates: parse_coordinates(chars.as_str())?,
+        })
+    }
+
+    /// The move as it would be typed, with the column in the given notation.
+    pub fn describe(&self, letter_columns: bool) -> String {
+        let letter = match self.kind {
+            MoveKind::Dig => 'd',
+            MoveKind::Flag => 'f',
+            MoveKind::Unflag => 'u',
+            MoveKind::Chord => 'c',
+        };
+        format!(
+            "{}{}",
+            letter,
+            describe_coordinates(self.coordinates, letter_columns)
+        )
+    }
+}
+
+/// The longest move list play accepts in one message.
+pub const MAX_MOVES: usize = 25;
+
+/// The letters of a one-based column, going A to Z and then AA, AB and so on like a
+/// spreadsheet.
+pub fn column_name(column: u32) -> String {
+    let mut name = vec![];
+    let mut column = column;
+
+    while column > 0 {
+        column -= 1;
+        name.push(b'A' + (column % 26) as u8);
+        column /= 26;
+    }
+
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+/// The one-based column named by some letters, in either case.
+pub fn parse_column(name: &str) -> Option<u32> {
+    if name.is_empty() || name.len() > 3 {
+        return None;
+    }
+
+    name.chars().try_fold(0, |column, letter| {
+        letter
+            .is_ascii_alphabetic()
+            .then(|| column * 26 + (letter.to_ascii_uppercase() as u32 - 'A' as u32 + 1))
+    })
+}
+
+/// Parses one-based coordinates written either as X,Y or as a column letter followed by a row,
+/// such as C7 or AA12. Zero is never a coordinate.
+pub fn parse_coordinates(text: &str) -> Option<(u32, u32)> {
+    let coordinates = match text.split_once(',') {
+        Some((x, y)) => (x.parse().ok()?, y.parse().ok()?),
+        None => {
+            let row_start = text.find(|c: char| c.is_ascii_digit())?;
+            let (column, row) = text.split_at(row_start);
+
+            (parse_column(column)?, row.parse().ok()?)
+        }
+    };
+
+    Some(coordinates).filter(|(x, y)| *x > 0 && *y > 0)
+}
+
+pub fn describe_coordinates(coordinates: (u32, u32), letter_columns: bool) -> String {
+    if letter_columns {
+        format!("{}{}", column_name(coordinates.0), coordinates.1)
+    } else {
+        format!("{},{}", coordinates.0, coordinates.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves_parse_their_kind_and_coordinates() {
+        assert_eq!(
+            PlannedMove::parse("d3,4"),
+            Some(PlannedMove {
+                kind: MoveKind::Dig,
+                coordinates: (3, 4)
+            })
+        );
+        assert_eq!(
+            PlannedMove::parse("F10,2").map(|planned| planned.kind),
+            Some(MoveKind::Flag)
+        );
+        assert_eq!(
+            PlannedMove::parse("u1,1").map(|planned| planned.kind),
+            Some(MoveKind::Unflag)
+        );
+        assert_eq!(
+            PlannedMove::parse("c5,6").map(|planned| planned.kind),
+            Some(MoveKind::Chord)
+        );
+    }
+
+    #[test]
+    fn malformed_moves_are_rejected() {
+        for text in ["", "d", "x3,4", "d3", "d3,", "d,4", "d-1,4", "d3,4,5"] {
+            assert_eq!(PlannedMove::parse(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn zero_is_not_a_coordinate() {
+        assert_eq!(parse_coordinates("0,4"), None);
+        assert_eq!(parse_coordinates("3,0"), None);
+        assert_eq!(PlannedMove::parse("d0,0"), None);
+    }
+
+    #[test]
+    fn column_names_round_trip_past_z() {
+        for (column, name) in [(1, "A"), (26, "Z"), (27, "AA"), (52, "AZ"), (53, "BA")] {
+            assert_eq!(column_name(column), name);
+            assert_eq!(parse_column(name), Some(column));
+            assert_eq!(parse_column(&name.to_lowercase()), Some(column));
+        }
+
+        assert_eq!(parse_column(""), None);
+        assert_eq!(parse_column("A1"), None);
+    }
+
+    #[test]
+    fn letter_coordinates_parse_like_numbered_ones() {
+        assert_eq!(parse_coordinates("C7"), Some((3, 7)));
+        assert_eq!(parse_coordinates("aa12"), Some((27, 12)));
+        assert_eq!(parse_coordinates("A0"), None);
+        assert_eq!(
+            PlannedMove::parse("fBA3"),
+            Some(PlannedMove {
+                kind: MoveKind::Flag,
+                coordinates: (53, 3)
+            })
+        );
+        assert_eq!(
+            PlannedMove {
+                kind: MoveKind::Chord,
+                coordinates: (28, 4)
+            }
+            .describe(true),
+            "cAB4"
+        );
+    }
+}
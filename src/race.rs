@@ -0,0 +1,101 @@
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use dashmap::DashMap;
+use serenity::prelude::TypeMapKey;
+
+use crate::board::GameKey;
+use crate::game::Tile;
+use crate::solver::Rating;
+
+/// Race games are named games, so they can run next to a player's other games.
+pub const RACE_GAME_NAME: &str = "race";
+
+/// How long a challenge can wait to be accepted.
+pub const CHALLENGE_MINUTES: i64 = 10;
+
+/// The mines of a race, shared by both players once either of them digs for the first time.
+pub struct RaceLayout {
+    pub tiles: Vec<Vec<Tile>>,
+    pub first_dig: (u32, u32),
+    pub rating: Option<Rating>,
+}
+
+#[derive(Clone)]
+pub struct RaceEntry {
+    pub name: String,
+    pub opponent: u64,
+    pub opponent_name: String,
+    /// The channel the race was accepted in, where both boards are.
+    pub channel: u64,
+    pub layout: Arc<Mutex<Option<RaceLayout>>>,
+}
+
+impl RaceEntry {
+    pub fn game_key(&self, player: u64) -> GameKey {
+        GameKey::player(player, self.channel, Some(RACE_GAME_NAME))
+    }
+}
+
+pub struct Challenge {
+    pub challenger: u64,
+    pub challenger_name: String,
+    pub game_settings: (u32, u32, u32),
+    pub issued: DateTime<Utc>,
+}
+
+impl Challenge {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now - self.issued >= Duration::minutes(CHALLENGE_MINUTES)
+    }
+}
+
+/// Each racing player's entry, keyed by their user id.
+pub struct RaceDataKey;
+
+impl TypeMapKey for RaceDataKey {
+    type Value = Arc<DashMap<u64, RaceEntry>>;
+}
+
+/// Challenges waiting to be accepted, keyed by the user id of the challenged player.
+pub struct ChallengeDataKey;
+
+impl TypeMapKey for ChallengeDataKey {
+    type Value = Arc<DashMap<u64, Challenge>>;
+}
+
+pub enum RaceFinish {
+    Cleared,
+    Exploded,
+    Stopped,
+    Abandoned,
+}
+
+impl RaceFinish {
+    pub fn description(&self, player_name: &str, opponent_name: &str) -> String {
+        match self {
+            RaceFinish::Cleared => format!("{} cleared the board first and wins!", player_name),
+            RaceFinish::Exploded => format!(
+                "{} hit a mine and forfeited, so {} wins!",
+                player_name, opponent_name
+            ),
+            RaceFinish::Stopped => format!(
+                "{} stopped their game and forfeited, so {} wins!",
+                player_name, opponent_name
+            ),
+            RaceFinish::Abandoned => format!(
+                "{} stopped playing and forfeited, so {} wins!",
+                player_name, opponent_name
+            ),
+        }
+    }
+
+    pub fn status(&self) -> &'static str {
+        match self {
+            RaceFinish::Cleared => "Cleared the board",
+            RaceFinish::Exploded => "Hit a mine",
+            RaceFinish::Stopped => "Gave up",
+            RaceFinish::Abandoned => "Abandoned",
+        }
+    }
+}
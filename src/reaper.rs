@@ -0,0 +1,192 @@
+use std::{borrow::Cow, collections::HashMap, sync::Arc, time::SystemTime};
+
+use chrono::{DateTime, Duration, Utc};
+use serenity::http::{AttachmentType, Http};
+use serenity::model::id::ChannelId;
+use serenity::prelude::{RwLock, TypeMap};
+
+use crate::board::{Board, GameDataKey, GameKey};
+use crate::daily::DailyOutcome;
+use crate::drawing;
+use crate::race::RaceFinish;
+use crate::spectate::SpectatorDataKey;
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How long a game can go without a move before its player is warned, and before it's closed.
+pub struct IdleLimits {
+    pub warning: Duration,
+    pub close: Duration,
+}
+
+enum IdleAction {
+    Warn,
+    Close,
+}
+
+/// Closes games nobody is playing anymore, so they don't stay in memory forever. Players get a
+/// warning first, and moving or resuming before the limit keeps the game alive.
+pub async fn run(data: Arc<RwLock<TypeMap>>, http: Arc<Http>, limits: IdleLimits) {
+    // The last activity each warned game had when its warning was sent, so a game is only
+    // warned once for every stretch of idling.
+    let mut warned = HashMap::<GameKey, DateTime<Utc>>::new();
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let data = data.read().await;
+        let game_data = data.get::<GameDataKey>().unwrap();
+        let now = DateTime::<Utc>::from(SystemTime::now());
+
+        warned.retain(|key, _| game_data.contains_key(key));
+        data.get::<SpectatorDataKey>()
+            .unwrap()
+            .retain(|key, _| game_data.contains_key(key));
+
+        let idle_games = game_data
+            .iter()
+            .filter_map(|entry| {
+                let last_activity = entry.value().last_activity();
+                let idle = now - last_activity;
+
+                let action = if idle >= limits.close {
+                    IdleAction::Close
+                } else if idle >= limits.warning && warned.get(entry.key()) != Some(&last_activity)
+                {
+                    IdleAction::Warn
+                } else {
+                    return None;
+                };
+
+                Some((entry.key().clone(), last_activity, action))
+            })
+            .collect::<Vec<_>>();
+
+        for (key, last_activity, action) in idle_games {
+            let channel = ChannelId(key.channel());
+
+            match action {
+                IdleAction::Warn => {
+                    channel
+                        .say(
+                            &http,
+                            format!(
+                                "{} been idle for {} minutes and will be closed in {} minutes unless someone makes a move.",
+                                game_owner(&key),
+                                limits.warning.num_minutes(),
+                                (limits.close - limits.warning).num_minutes()
+                            ),
+                        )
+                        .await
+                        .ok();
+                    warned.insert(key, last_activity);
+                }
+                IdleAction::Close => {
+                    warned.remove(&key);
+                    close_game(&http, &data, key, channel, limits.close).await;
+                }
+            }
+        }
+    }
+}
+
+fn game_owner(key: &GameKey) -> String {
+    match key {
+        GameKey::Player {
+            player,
+            name: Some(name),
+            ..
+        } => format!("<@{}>, your {} game has", player, name),
+        GameKey::Player { player, .. } => format!("<@{}>, your game has", player),
+        GameKey::Channel(_) => "This channel's game has".to_string(),
+    }
+}
+
+async fn close_game(
+    http: &Arc<Http>,
+    data: &TypeMap,
+    key: GameKey,
+    channel: ChannelId,
+    close_after: Duration,
+) {
+    let game_data = data.get::<GameDataKey>().unwrap();
+    let now = DateTime::<Utc>::from(SystemTime::now());
+    let race = crate::find_race(data, &key);
+
+    // Someone may have moved since the idle games were listed, so idleness is checked again while
+    // the board is held. Games outside races are taken out of the map under the same guard, so no
+    // move can land on them while they're being closed. The board is drawn before anything is
+    // sent, so no guard into the map is held while waiting on Discord.
+    let idle = |board: &dyn Board| now - board.last_activity() >= close_after;
+    let draw = |board: &dyn Board| {
+        let image = drawing::scale_to_tile_size(board.render())
+            .encode_png()
+            .unwrap();
+
+        (image, (now - board.last_activity()).num_minutes())
+    };
+
+    let (closed, removed) = if race.is_some() {
+        let closed = game_data
+            .get_mut(&key)
+            .filter(|board| idle(&***board))
+            .map(|board| draw(&**board));
+        (closed, None)
+    } else {
+        match game_data.remove_if(&key, |_, board| idle(&**board)) {
+            Some((_, board)) => (Some(draw(&*board)), Some(board)),
+            None => (None, None),
+        }
+    };
+
+    let (image, idle_minutes) = match closed {
+        Some(closed) => closed,
+        None => return,
+    };
+
+    channel
+        .say(
+            http,
+            format!(
+                "{} was closed after {} minutes without a move.",
+                match &key {
+                    GameKey::Player {
+                        player,
+                        name: Some(name),
+                        ..
+                    } => format!("<@{}>'s {} game", player, name),
+                    GameKey::Player { player, .. } => format!("<@{}>'s game", player),
+                    GameKey::Channel(_) => "This channel's game".to_string(),
+                },
+                idle_minutes
+            ),
+        )
+        .await
+        .ok();
+
+    let attachment = AttachmentType::Bytes {
+        data: Cow::Owned(image),
+        filename: "File.png".to_string(),
+    };
+    channel
+        .send_message(http, |m| m.add_file(attachment))
+        .await
+        .ok();
+
+    let player = match key.owner() {
+        Some(player) => player,
+        None => return,
+    };
+
+    if let Some(race) = race {
+        crate::finish_race(http, channel, data, player, &race, RaceFinish::Abandoned)
+            .await
+            .ok();
+        return;
+    }
+
+    if let Some(board) = removed {
+        crate::finish_daily_attempt(data, player, board.as_ref(), DailyOutcome::Abandoned);
+    }
+}
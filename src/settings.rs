@@ -0,0 +1,55 @@
+use std::{collections::HashMap, sync::Arc, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use serenity::prelude::TypeMapKey;
+
+use crate::store;
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// Preferences a player has opted into. Anything missing from settings.json keeps its default.
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct UserSettings {
+    /// Dig around numbers that already have enough flags after every dig.
+    #[serde(default)]
+    pub auto_dig: bool,
+    /// Label columns with letters, so coordinates read like C7 instead of 3 7.
+    #[serde(default)]
+    pub letter_columns: bool,
+}
+
+#[derive(Default)]
+pub struct SettingsStore {
+    users: Mutex<HashMap<u64, UserSettings>>,
+}
+
+impl SettingsStore {
+    pub fn get(&self, user_id: u64) -> UserSettings {
+        self.users
+            .lock()
+            .unwrap()
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn update(&self, user_id: u64, change: impl FnOnce(&mut UserSettings)) {
+        change(self.users.lock().unwrap().entry(user_id).or_default());
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        store::save(SETTINGS_FILE, &*self.users.lock().unwrap())
+    }
+}
+
+pub fn load_settings() -> SettingsStore {
+    SettingsStore {
+        users: Mutex::new(store::load(SETTINGS_FILE)),
+    }
+}
+
+pub struct SettingsDataKey;
+
+impl TypeMapKey for SettingsDataKey {
+    type Value = Arc<SettingsStore>;
+}
@@ -0,0 +1,343 @@
+use serenity::client::Context;
+use serenity::http::Http;
+use serenity::model::interactions::application_command::{
+    ApplicationCommand, ApplicationCommandInteraction, ApplicationCommandInteractionDataOption,
+    ApplicationCommandInteractionDataOptionValue, ApplicationCommandOptionType,
+};
+use serenity::model::interactions::autocomplete::AutocompleteInteraction;
+use serenity::model::interactions::{
+    InteractionApplicationCommandCallbackDataFlags, InteractionResponseType,
+};
+
+use crate::board::{self, GameDataKey};
+use crate::guilds::{self, GuildConfigDataKey};
+use crate::invocation::Invocation;
+use crate::moves::{self, MoveKind, PlannedMove};
+
+const POLICIES: &[(&str, &str)] = &[
+    ("No protection", "nosafety"),
+    ("Safe first tile", "safetile"),
+    ("Guaranteed opening", "opening"),
+    ("Safe radius of 1", "radius=1"),
+    ("Safe radius of 2", "radius=2"),
+    ("Safe radius of 3", "radius=3"),
+    ("Safe radius of 4", "radius=4"),
+];
+
+const TIERS: &[(&str, &str)] = &[
+    ("Trivial", "trivial"),
+    ("Logical", "logical"),
+    ("Hard logical", "hardlogical"),
+    ("Needs guessing", "guessing"),
+];
+
+/// Replaces the bot's slash commands with the ones handled here. This is done once per run of the
+/// bot, not on every reconnect.
+pub async fn register(http: &Http) -> serenity::Result<()> {
+    ApplicationCommand::set_global_application_commands(http, |commands| {
+        commands.create_application_command(|command| {
+            command
+                .name("startgame")
+                .description("Start a game of minesweeper")
+                .create_option(|option| {
+                    option
+                        .name("difficulty")
+                        .description("How big the board is and how many mines it has")
+                        .kind(ApplicationCommandOptionType::String)
+                        .required(true)
+                        .set_autocomplete(true)
+                })
+                .create_option(|option| {
+                    option
+                        .name("mode")
+                        .description("The game mode, classic if not given")
+                        .kind(ApplicationCommandOptionType::String);
+                    for variant in board::VARIANTS {
+                        option.add_string_choice(variant.name, variant.name);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("opponent")
+                        .description("Who to play against, for modes with two players")
+                        .kind(ApplicationCommandOptionType::User)
+                })
+                .create_option(|option| {
+                    option
+                        .name("first_dig")
+                        .description("How the first dig is protected")
+                        .kind(ApplicationCommandOptionType::String);
+                    for (name, value) in POLICIES {
+                        option.add_string_choice(name, value);
+                    }
+                    option
+                })
+                .create_option(|option| {
+                    option
+                        .name("rating")
+                        .description("How much reasoning the board should need")
+                        .kind(ApplicationCommandOptionType::String);
+                    for (name, value) in TIERS {
+                        option.add_string_choice(name, value);
+                    }
+                    option
+                })
+        });
+
+        for (name, description) in [
+            ("dig", "Dig a tile"),
+            ("flag", "Flag a tile"),
+            ("unflag", "Remove a flag"),
+        ] {
+            commands.create_application_command(|command| {
+                command
+                    .name(name)
+                    .description(description)
+                    // A string rather than an integer, since boards with letter columns are
+                    // played by typing the column's letter.
+                    .create_option(|option| {
+                        option
+                            .name("x")
+                            .description("The column, counting from 1 on the left, or its letter")
+                            .kind(ApplicationCommandOptionType::String)
+                            .required(true)
+                    })
+                    .create_option(|option| {
+                        option
+                            .name("y")
+                            .description("The row, counting from 1 at the top")
+                            .kind(ApplicationCommandOptionType::Integer)
+                            .required(true)
+                            .min_int_value(1)
+                            .max_int_value(guilds::MAX_BOARD_SIDE as i32)
+                    })
+            });
+        }
+
+        commands
+            .create_application_command(|command| {
+                command
+                    .name("resend")
+                    .description("Send your current board again")
+            })
+            .create_application_command(|command| {
+                command
+                    .name("stopgame")
+                    .description("End your current game")
+            })
+    })
+    .await?;
+
+    Ok(())
+}
+
+fn find_option<'a>(
+    options: &'a [ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Option<&'a ApplicationCommandInteractionDataOption> {
+    options.iter().find(|option| option.name == name)
+}
+
+fn string_option<'a>(
+    options: &'a [ApplicationCommandInteractionDataOption],
+    name: &str,
+) -> Option<&'a str> {
+    find_option(options, name)?.value.as_ref()?.as_str()
+}
+
+fn integer_option(options: &[ApplicationCommandInteractionDataOption], name: &str) -> Option<u32> {
+    let value = find_option(options, name)?.value.as_ref()?.as_u64()?;
+    u32::try_from(value).ok()
+}
+
+/// Reads the x and y options of a move, where x is either a column number or its letters.
+fn move_coordinates(options: &[ApplicationCommandInteractionDataOption]) -> Option<(u32, u32)> {
+    let column = string_option(options, "x")?.trim();
+    let column = column
+        .parse()
+        .ok()
+        .or_else(|| moves::parse_column(column))?;
+
+    Some((column, integer_option(options, "y")?))
+}
+
+/// Checks a move against the board it would be played on, so a mistake is only shown to the
+/// player who made it.
+async fn check_move(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    coordinates: Option<(u32, u32)>,
+) -> Result<(u32, u32), String> {
+    let data = ctx.data.read().await;
+    let board = crate::find_game_key(&data, interaction.user.id.0, interaction.channel_id)
+        .and_then(|key| data.get::<GameDataKey>().unwrap().get(&key))
+        .map(|board| (board.dimensions(), board.letter_columns()));
+
+    let (dimensions, letter_columns) = match board {
+        Some(board) => board,
+        None => {
+            return Err(
+                "You don't have any running games! Use the command startgame [difficulty] to start a game."
+                    .to_string(),
+            )
+        }
+    };
+
+    match coordinates {
+        Some(coordinates) if crate::coordinates_in_bounds(dimensions, coordinates) => {
+            Ok(coordinates)
+        }
+        Some(coordinates) => Err(format!(
+            "{} isn't on your board, which ends at {}.",
+            moves::describe_coordinates(coordinates, letter_columns),
+            moves::describe_coordinates(dimensions, letter_columns)
+        )),
+        None => Err("x needs to be a column number or letters, such as 3 or C.".to_string()),
+    }
+}
+
+async fn respond_privately(
+    ctx: &Context,
+    interaction: &ApplicationCommandInteraction,
+    content: impl ToString,
+) {
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response
+                .kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|message| {
+                    message
+                        .content(content)
+                        .flags(InteractionApplicationCommandCallbackDataFlags::EPHEMERAL)
+                })
+        })
+        .await
+        .ok();
+}
+
+/// Runs a slash command through the same code as its ~ command. The interaction is answered
+/// straight away so Discord doesn't give up on it, and that answer is removed once the command
+/// has posted its replies to the channel.
+pub async fn run(ctx: &Context, interaction: &ApplicationCommandInteraction) {
+    let allowed = ctx
+        .data
+        .read()
+        .await
+        .get::<GuildConfigDataKey>()
+        .unwrap()
+        .get_for(interaction.guild_id)
+        .allows_channel(interaction.channel_id.0);
+
+    if !allowed {
+        respond_privately(ctx, interaction, "The bot can't be used in this channel!").await;
+        return;
+    }
+
+    let options = &interaction.data.options;
+    let kind = match interaction.data.name.as_str() {
+        "dig" => Some(MoveKind::Dig),
+        "flag" => Some(MoveKind::Flag),
+        "unflag" => Some(MoveKind::Unflag),
+        _ => None,
+    };
+
+    let planned = match kind {
+        Some(kind) => match check_move(ctx, interaction, move_coordinates(options)).await {
+            Ok(coordinates) => Some(PlannedMove { kind, coordinates }),
+            Err(reason) => {
+                respond_privately(ctx, interaction, reason).await;
+                return;
+            }
+        },
+        None => None,
+    };
+
+    interaction
+        .create_interaction_response(&ctx.http, |response| {
+            response.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+        })
+        .await
+        .ok();
+
+    let mut invocation = Invocation {
+        author: interaction.user.clone(),
+        channel_id: interaction.channel_id,
+        guild_id: interaction.guild_id,
+        mentions: vec![],
+    };
+
+    if let Some(ApplicationCommandInteractionDataOptionValue::User(user, _)) =
+        find_option(options, "opponent").and_then(|option| option.resolved.as_ref())
+    {
+        invocation.mentions.push(user.clone());
+    }
+
+    match interaction.data.name.as_str() {
+        "startgame" => {
+            let args = ["mode", "difficulty", "first_dig", "rating"]
+                .iter()
+                .filter_map(|name| string_option(options, name))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            crate::run_startgame(ctx, &invocation, &args).await;
+        }
+        "resend" => crate::run_resend(ctx, &invocation).await,
+        "stopgame" => crate::run_stopgame(ctx, &invocation).await,
+        _ => {
+            if let Some(planned) = planned {
+                crate::apply_moves(ctx, &invocation, &[planned], vec![]).await;
+            }
+        }
+    }
+
+    interaction
+        .delete_original_interaction_response(&ctx.http)
+        .await
+        .ok();
+}
+
+/// Suggests difficulties and server presets matching what's been typed so far.
+pub async fn autocomplete(ctx: &Context, interaction: &AutocompleteInteraction) {
+    let guild_config = ctx
+        .data
+        .read()
+        .await
+        .get::<GuildConfigDataKey>()
+        .unwrap()
+        .get_for(interaction.guild_id);
+
+    let typed = interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.focused)
+        .and_then(|option| option.value.as_ref()?.as_str())
+        .unwrap_or("")
+        .to_ascii_lowercase();
+
+    interaction
+        .create_autocomplete_response(&ctx.http, |response| {
+            // Discord shows at most 25 suggestions.
+            for difficulty in guild_config
+                .difficulty_names()
+                .into_iter()
+                .filter(|difficulty| difficulty.starts_with(&typed))
+                .take(25)
+            {
+                let (width, height, number_of_mines) =
+                    guild_config.difficulty_settings(difficulty).unwrap();
+                response.add_string_choice(
+                    format!(
+                        "{} ({} by {}, {} mines)",
+                        difficulty, width, height, number_of_mines
+                    ),
+                    difficulty,
+                );
+            }
+            response
+        })
+        .await
+        .ok();
+}
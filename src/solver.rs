@@ -0,0 +1,674 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::game::{Game, GameState, Tile};
+
+/// Frontier groups with more unknown tiles than this are never tried exhaustively.
+const MAX_EXHAUSTIVE_TILES: usize = 32;
+
+/// How much reasoning a deduction needed, from one number on its own up to trying every
+/// arrangement of mines along part of the frontier.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum DeductionLevel {
+    Single,
+    Pair,
+    Exhaustive,
+}
+
+/// Tiles that are certainly safe or certainly mines, given what the player can see.
+pub struct Deduction {
+    pub safe: Vec<(u32, u32)>,
+    pub mines: Vec<(u32, u32)>,
+    pub level: DeductionLevel,
+}
+
+/// How a board went when the solver played it.
+#[derive(Clone, Copy, Debug)]
+pub struct SolveReport {
+    pub won: bool,
+    pub guesses: u32,
+    /// The hardest deduction the board needed, or None if it never needed one.
+    pub hardest: Option<DeductionLevel>,
+}
+
+/// A revealed number: exactly `mines` of `tiles` are mines.
+struct Constraint {
+    tiles: Vec<(u32, u32)>,
+    mines: u32,
+}
+
+fn neighbours(game: &Game, position: (u32, u32)) -> impl Iterator<Item = (u32, u32)> {
+    let (width, height) = (game.width as i32, game.height as i32);
+
+    (-1..=1)
+        .flat_map(|y| (-1..=1).map(move |x| (x, y)))
+        .filter(|offset| *offset != (0, 0))
+        .map(move |(x, y)| (position.0 as i32 + x, position.1 as i32 + y))
+        .filter(move |(x, y)| *x >= 0 && *y >= 0 && *x < width && *y < height)
+        .map(|(x, y)| (x as u32, y as u32))
+}
+
+fn is_unknown(game: &Game, known_mines: &HashSet<(u32, u32)>, position: (u32, u32)) -> bool {
+    !game.tiles[position.1 as usize][position.0 as usize].is_revealed
+        && !known_mines.contains(&position)
+}
+
+fn constraints(game: &Game, known_mines: &HashSet<(u32, u32)>) -> Vec<Constraint> {
+    let mut constraints = vec![];
+
+    for (y, row) in game.tiles.iter().enumerate() {
+        for (x, tile) in row.iter().enumerate() {
+            if !tile.is_revealed || tile.is_mine || tile.adjacent_mines == 0 {
+                continue;
+            }
+
+            let position = (x as u32, y as u32);
+            let mut tiles = vec![];
+            let mut found_mines = 0;
+
+            for neighbour in neighbours(game, position) {
+                if known_mines.contains(&neighbour) {
+                    found_mines += 1;
+                } else if is_unknown(game, known_mines, neighbour) {
+                    tiles.push(neighbour);
+                }
+            }
+
+            if !tiles.is_empty() {
+                constraints.push(Constraint {
+                    tiles,
+                    mines: tile.adjacent_mines.saturating_sub(found_mines),
+                });
+            }
+        }
+    }
+
+    constraints
+}
+
+fn deduction(
+    safe: HashSet<(u32, u32)>,
+    mines: HashSet<(u32, u32)>,
+    level: DeductionLevel,
+) -> Option<Deduction> {
+    if safe.is_empty() && mines.is_empty() {
+        return None;
+    }
+
+    let mut safe = safe.into_iter().collect::<Vec<_>>();
+    let mut mines = mines.into_iter().collect::<Vec<_>>();
+    safe.sort_unstable();
+    mines.sort_unstable();
+
+    Some(Deduction { safe, mines, level })
+}
+
+/// Numbers that are already satisfied, or that need every tile around them to be a mine.
+fn single_deduction(
+    game: &Game,
+    known_mines: &HashSet<(u32, u32)>,
+    constraints: &[Constraint],
+) -> Option<Deduction> {
+    let mut safe = HashSet::new();
+    let mut mines = HashSet::new();
+
+    if known_mines.len() as u32 >= game.number_of_mines {
+        safe.extend(
+            (0..game.height)
+                .flat_map(|y| (0..game.width).map(move |x| (x, y)))
+                .filter(|position| is_unknown(game, known_mines, *position)),
+        );
+    }
+
+    for constraint in constraints {
+        if constraint.mines == 0 {
+            safe.extend(&constraint.tiles);
+        } else if constraint.mines as usize == constraint.tiles.len() {
+            mines.extend(&constraint.tiles);
+        }
+    }
+
+    deduction(safe, mines, DeductionLevel::Single)
+}
+
+/// Two overlapping numbers where the difference between them decides the tiles only one of them
+/// touches, like the classic 1-2 pattern along a wall.
+fn pair_deduction(constraints: &[Constraint]) -> Option<Deduction> {
+    let mut safe = HashSet::new();
+    let mut mines = HashSet::new();
+
+    for a in constraints {
+        for b in constraints {
+            if !a.tiles.iter().any(|tile| b.tiles.contains(tile)) {
+                continue;
+            }
+
+            let only_a = a
+                .tiles
+                .iter()
+                .filter(|tile| !b.tiles.contains(tile))
+                .collect::<Vec<_>>();
+            let only_b = b
+                .tiles
+                .iter()
+                .filter(|tile| !a.tiles.contains(tile))
+                .collect::<Vec<_>>();
+
+            if only_b.is_empty() || b.mines < a.mines {
+                continue;
+            }
+
+            let difference = b.mines - a.mines;
+
+            if difference as usize == only_b.len() {
+                mines.extend(only_b);
+                safe.extend(only_a);
+            } else if difference == 0 && only_a.is_empty() {
+                safe.extend(only_b);
+            }
+        }
+    }
+
+    deduction(safe, mines, DeductionLevel::Pair)
+}
+
+/// Tries every arrangement of mines in each independent part of the frontier, keeping the tiles
+/// that are safe or mined in all of them.
+fn exhaustive_deduction(constraints: &[Constraint]) -> Option<Deduction> {
+    let mut safe = HashSet::new();
+    let mut mines = HashSet::new();
+
+    for group in frontier_groups(constraints) {
+        // Neighbouring tiles are kept next to each other so contradictions are found early.
+        let mut tiles = vec![];
+        for tile in group
+            .iter()
+            .flat_map(|index| constraints[*index].tiles.iter().copied())
+        {
+            if !tiles.contains(&tile) {
+                tiles.push(tile);
+            }
+        }
+
+        if tiles.len() > MAX_EXHAUSTIVE_TILES {
+            continue;
+        }
+
+        let group_constraints = group
+            .iter()
+            .map(|index| {
+                let constraint = &constraints[*index];
+                (
+                    constraint
+                        .tiles
+                        .iter()
+                        .map(|tile| tiles.iter().position(|t| t == tile).unwrap())
+                        .collect::<Vec<_>>(),
+                    constraint.mines,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut search = Search {
+            constraints: &group_constraints,
+            constraints_of: (0..tiles.len())
+                .map(|tile| {
+                    (0..group_constraints.len())
+                        .filter(|index| group_constraints[*index].0.contains(&tile))
+                        .collect()
+                })
+                .collect(),
+            assignment: vec![None; tiles.len()],
+            solutions: 0,
+            mine_counts: vec![0; tiles.len()],
+        };
+        search.run(0);
+
+        if search.solutions == 0 {
+            continue;
+        }
+
+        for (tile, count) in tiles.iter().zip(&search.mine_counts) {
+            if *count == 0 {
+                safe.insert(*tile);
+            } else if *count == search.solutions {
+                mines.insert(*tile);
+            }
+        }
+    }
+
+    deduction(safe, mines, DeductionLevel::Exhaustive)
+}
+
+/// Splits the constraints into groups that share no tiles with each other.
+fn frontier_groups(constraints: &[Constraint]) -> Vec<Vec<usize>> {
+    let mut group_of = vec![usize::MAX; constraints.len()];
+    let mut groups = vec![];
+
+    for start in 0..constraints.len() {
+        if group_of[start] != usize::MAX {
+            continue;
+        }
+
+        let mut group = vec![start];
+        group_of[start] = groups.len();
+        let mut next = 0;
+
+        while next < group.len() {
+            let current = &constraints[group[next]];
+            next += 1;
+
+            for (index, other) in constraints.iter().enumerate() {
+                if group_of[index] == usize::MAX
+                    && other.tiles.iter().any(|tile| current.tiles.contains(tile))
+                {
+                    group_of[index] = groups.len();
+                    group.push(index);
+                }
+            }
+        }
+
+        groups.push(group);
+    }
+
+    groups
+}
+
+struct Search<'a> {
+    constraints: &'a [(Vec<usize>, u32)],
+    /// The constraints each tile is part of.
+    constraints_of: Vec<Vec<usize>>,
+    assignment: Vec<Option<bool>>,
+    solutions: u64,
+    mine_counts: Vec<u64>,
+}
+
+impl Search<'_> {
+    /// Whether the constraints around a tile can still be met after it was assigned.
+    fn is_consistent(&self, tile: usize) -> bool {
+        self.constraints_of[tile].iter().all(|index| {
+            let (tiles, mines) = &self.constraints[*index];
+            let placed = tiles
+                .iter()
+                .filter(|tile| self.assignment[**tile] == Some(true))
+                .count() as u32;
+            let open = tiles
+                .iter()
+                .filter(|tile| self.assignment[**tile].is_none())
+                .count() as u32;
+
+            placed <= *mines && placed + open >= *mines
+        })
+    }
+
+    fn run(&mut self, tile: usize) {
+        if tile == self.assignment.len() {
+            self.solutions += 1;
+            for (count, assigned) in self.mine_counts.iter_mut().zip(&self.assignment) {
+                if *assigned == Some(true) {
+                    *count += 1;
+                }
+            }
+            return;
+        }
+
+        for is_mine in [false, true] {
+            self.assignment[tile] = Some(is_mine);
+            if self.is_consistent(tile) {
+                self.run(tile + 1);
+            }
+        }
+        self.assignment[tile] = None;
+    }
+}
+
+/// Finds the easiest deduction available on the board. `known_mines` are the tiles the solver
+/// has already proven to be mines.
+pub fn deduce(game: &Game, known_mines: &HashSet<(u32, u32)>) -> Option<Deduction> {
+    let constraints = constraints(game, known_mines);
+
+    single_deduction(game, known_mines, &constraints)
+        .or_else(|| pair_deduction(&constraints))
+        .or_else(|| exhaustive_deduction(&constraints))
+}
+
+/// The unknown tiles least likely to be mines, judging each tile by the worst number around it.
+fn safest_guesses(game: &Game, known_mines: &HashSet<(u32, u32)>) -> Vec<(u32, u32)> {
+    let unknown = (0..game.height)
+        .flat_map(|y| (0..game.width).map(move |x| (x, y)))
+        .filter(|position| is_unknown(game, known_mines, *position))
+        .collect::<Vec<_>>();
+
+    let remaining_mines = game
+        .number_of_mines
+        .saturating_sub(known_mines.len() as u32);
+    let density = remaining_mines as f64 / unknown.len().max(1) as f64;
+
+    let mut risk = HashMap::new();
+    for constraint in constraints(game, known_mines) {
+        let constraint_risk = constraint.mines as f64 / constraint.tiles.len() as f64;
+
+        for tile in constraint.tiles {
+            let tile_risk = risk.entry(tile).or_insert(0.0f64);
+            *tile_risk = tile_risk.max(constraint_risk);
+        }
+    }
+
+    let tile_risk = |position: &(u32, u32)| *risk.get(position).unwrap_or(&density);
+    let lowest = unknown.iter().map(tile_risk).fold(f64::INFINITY, f64::min);
+
+    unknown
+        .iter()
+        .filter(|position| tile_risk(position) <= lowest)
+        .copied()
+        .collect()
+}
+
+/// Digs everything that can be proven safe, asking `guess` for a tile whenever nothing can be.
+fn play(
+    game: &mut Game,
+    mut guess: impl FnMut(&Game, &HashSet<(u32, u32)>) -> Option<(u32, u32)>,
+) -> SolveReport {
+    let mut report = SolveReport {
+        won: false,
+        guesses: 0,
+        hardest: None,
+    };
+
+    if game.state == GameState::NotStarted && game.dig((game.width / 2, game.height / 2)).is_err() {
+        return report;
+    }
+
+    let mut known_mines = HashSet::new();
+
+    while game.state == GameState::Playing {
+        match deduce(game, &known_mines) {
+            Some(deduction) => {
+                report.hardest = report.hardest.max(Some(deduction.level));
+                known_mines.extend(deduction.mines);
+
+                for position in deduction.safe {
+                    if game.state != GameState::Playing {
+                        break;
+                    }
+                    game.dig(position).ok();
+                }
+            }
+            None => match guess(game, &known_mines) {
+                Some(position) => {
+                    report.guesses += 1;
+                    game.dig(position).ok();
+                }
+                None => break,
+            },
+        }
+    }
+
+    report.won = game.state == GameState::Won;
+    report
+}
+
+/// Plays the game until it's won or lost, guessing one of the safest looking tiles when nothing
+/// can be proven. A game that hasn't started is opened in the middle.
+pub fn solve(game: &mut Game, rng: &mut impl Rng) -> SolveReport {
+    play(game, |game, known_mines| {
+        safest_guesses(game, known_mines).choose(rng).copied()
+    })
+}
+
+/// How hard a board is to clear without luck.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Tier {
+    Trivial,
+    Logical,
+    HardLogical,
+    NeedsGuessing,
+}
+
+impl Tier {
+    /// Parses the names used by startgame: trivial, logical, hardlogical and guessing.
+    pub fn parse(tier: &str) -> Option<Self> {
+        match tier {
+            "trivial" => Some(Tier::Trivial),
+            "logical" => Some(Tier::Logical),
+            "hardlogical" => Some(Tier::HardLogical),
+            "guessing" => Some(Tier::NeedsGuessing),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tier::Trivial => write!(f, "Trivial"),
+            Tier::Logical => write!(f, "Logical"),
+            Tier::HardLogical => write!(f, "Hard logical"),
+            Tier::NeedsGuessing => write!(f, "Needs guessing"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rating {
+    pub tier: Tier,
+    pub hardest: Option<DeductionLevel>,
+    pub guesses: u32,
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tier {
+            Tier::NeedsGuessing => write!(
+                f,
+                "{} ({} forced guess{})",
+                self.tier,
+                self.guesses,
+                if self.guesses == 1 { "" } else { "es" }
+            ),
+            _ => write!(f, "{}", self.tier),
+        }
+    }
+}
+
+/// Rates a layout by playing it from its first dig. Forced guesses always land on a safe tile, so
+/// the whole board gets rated rather than just the part before the first wrong guess.
+pub fn rate_layout(tiles: &[Vec<Tile>], number_of_mines: u32, first_dig: (u32, u32)) -> Rating {
+    let mut game = Game::new(tiles[0].len() as u32, tiles.len() as u32, number_of_mines);
+    game.start_with_layout(tiles, first_dig);
+
+    let report = play(&mut game, |game, known_mines| {
+        let is_safe =
+            |position: &(u32, u32)| !game.tiles[position.1 as usize][position.0 as usize].is_mine;
+
+        safest_guesses(game, known_mines)
+            .into_iter()
+            .find(is_safe)
+            .or_else(|| {
+                (0..game.height)
+                    .flat_map(|y| (0..game.width).map(move |x| (x, y)))
+                    .filter(|position| is_unknown(game, known_mines, *position))
+                    .find(is_safe)
+            })
+    });
+
+    let tier = match report.hardest {
+        _ if report.guesses > 0 => Tier::NeedsGuessing,
+        Some(DeductionLevel::Exhaustive) => Tier::HardLogical,
+        Some(DeductionLevel::Pair) => Tier::Logical,
+        _ => Tier::Trivial,
+    };
+
+    Rating {
+        tier,
+        hardest: report.hardest,
+        guesses: report.guesses,
+    }
+}
+
+/// The rating of a game that has already been started.
+pub fn rate(game: &Game) -> Option<Rating> {
+    game.first_dig
+        .map(|first_dig| rate_layout(&game.tiles, game.number_of_mines, first_dig))
+}
+
+/// The minimum number of clicks needed to clear the board: one for every opening, plus one for
+/// every number that isn't on the edge of an opening.
+pub fn three_bv(game: &Game) -> u32 {
+    let mut counted = vec![vec![false; game.width as usize]; game.height as usize];
+    let mut clicks = 0;
+
+    for y in 0..game.height {
+        for x in 0..game.width {
+            let tile = &game.tiles[y as usize][x as usize];
+
+            if tile.is_mine || tile.adjacent_mines != 0 || counted[y as usize][x as usize] {
+                continue;
+            }
+
+            clicks += 1;
+            let mut opening = vec![(x, y)];
+            counted[y as usize][x as usize] = true;
+
+            while let Some(position) = opening.pop() {
+                if game.tiles[position.1 as usize][position.0 as usize].adjacent_mines != 0 {
+                    continue;
+                }
+
+                for neighbour in neighbours(game, position) {
+                    if !counted[neighbour.1 as usize][neighbour.0 as usize] {
+                        counted[neighbour.1 as usize][neighbour.0 as usize] = true;
+                        opening.push(neighbour);
+                    }
+                }
+            }
+        }
+    }
+
+    clicks
+        + game
+            .tiles
+            .iter()
+            .flatten()
+            .zip(counted.iter().flatten())
+            .filter(|(tile, counted)| !tile.is_mine && !**counted)
+            .count() as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a board from rows where `*` is a hidden mine, `#` a hidden safe tile and `.` a
+    /// revealed one.
+    fn board(rows: &[&str]) -> Game {
+        let mines = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .filter(|c| *c == '*');
+        let mut game = Game::new(
+            rows[0].len() as u32,
+            rows.len() as u32,
+            mines.count() as u32,
+        );
+
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let tile = &mut game.tiles[y][x];
+                tile.is_mine = c == '*';
+                tile.is_revealed = c == '.';
+            }
+        }
+
+        for y in 0..game.height {
+            for x in 0..game.width {
+                game.tiles[y as usize][x as usize].adjacent_mines = neighbours(&game, (x, y))
+                    .filter(|(x, y)| game.tiles[*y as usize][*x as usize].is_mine)
+                    .count()
+                    as u32;
+            }
+        }
+
+        game
+    }
+
+    #[test]
+    fn pair_deduction_solves_the_1_2_1_wall() {
+        let game = board(&["*#*", "..."]);
+
+        let deduction = deduce(&game, &HashSet::new()).unwrap();
+
+        assert_eq!(deduction.level, DeductionLevel::Pair);
+        assert_eq!(deduction.mines, vec![(0, 0), (2, 0)]);
+        assert!(deduction.safe.is_empty());
+    }
+
+    #[test]
+    fn exhaustive_deduction_finds_the_safe_tile_between_the_mines() {
+        let game = board(&["*#*", "..."]);
+
+        let deduction = exhaustive_deduction(&constraints(&game, &HashSet::new())).unwrap();
+
+        assert_eq!(deduction.level, DeductionLevel::Exhaustive);
+        assert_eq!(deduction.mines, vec![(0, 0), (2, 0)]);
+        assert_eq!(deduction.safe, vec![(1, 0)]);
+    }
+
+    #[test]
+    fn known_mines_satisfy_numbers() {
+        let game = board(&["*#*", "..."]);
+        let known_mines = HashSet::from([(0, 0), (2, 0)]);
+
+        let deduction = deduce(&game, &known_mines).unwrap();
+
+        assert_eq!(deduction.level, DeductionLevel::Single);
+        assert_eq!(deduction.safe, vec![(1, 0)]);
+        assert!(deduction.mines.is_empty());
+    }
+
+    #[test]
+    fn nothing_is_deduced_from_a_coin_flip() {
+        let game = board(&["*#", ".."]);
+
+        assert!(deduce(&game, &HashSet::new()).is_none());
+    }
+
+    #[test]
+    fn three_bv_counts_openings_and_lone_numbers() {
+        // The opening on the right clears the 1 next to it, but the 2 between the mines needs a
+        // click of its own.
+        assert_eq!(three_bv(&board(&["*#*##"])), 2);
+        assert_eq!(three_bv(&board(&["*##", "###", "###"])), 1);
+        assert_eq!(three_bv(&board(&["#*#"])), 2);
+    }
+
+    #[test]
+    fn tier_names_parse_to_their_tiers() {
+        for (name, tier) in [
+            ("trivial", Tier::Trivial),
+            ("logical", Tier::Logical),
+            ("hardlogical", Tier::HardLogical),
+            ("guessing", Tier::NeedsGuessing),
+        ] {
+            assert_eq!(Tier::parse(name), Some(tier));
+        }
+
+        assert_eq!(Tier::parse("easy"), None);
+        assert_eq!(Tier::HardLogical.to_string(), "Hard logical");
+    }
+
+    #[test]
+    fn layouts_are_rated_by_what_they_need() {
+        let open = board(&["*##", "###", "###"]);
+        let rating = rate_layout(&open.tiles, open.number_of_mines, (2, 2));
+        assert_eq!(rating.tier, Tier::Trivial);
+        assert_eq!(rating.hardest, None);
+
+        let coin_flip = board(&["*#", "##"]);
+        let rating = rate_layout(&coin_flip.tiles, coin_flip.number_of_mines, (1, 1));
+        assert_eq!(rating.tier, Tier::NeedsGuessing);
+        assert!(rating.guesses > 0);
+    }
+}
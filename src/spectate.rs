@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use serenity::http::{AttachmentType, Http};
+use serenity::model::id::ChannelId;
+use serenity::prelude::{TypeMap, TypeMapKey};
+
+use crate::board::{Board, GameDataKey, GameKey};
+use crate::board_message::{self, BoardMessageError};
+use crate::drawing;
+
+/// A channel following someone else's game.
+#[derive(Clone)]
+pub struct Spectator {
+    pub channel: u64,
+    /// The mirrored board, which every move edits.
+    pub message: Option<u64>,
+}
+
+/// The spectators of each watched game.
+pub struct SpectatorDataKey;
+
+impl TypeMapKey for SpectatorDataKey {
+    type Value = Arc<DashMap<GameKey, Vec<Spectator>>>;
+}
+
+/// Draws a board the way spectators see it: the mines stay hidden until the game is over.
+pub fn render_for_spectators(board: &dyn Board) -> Vec<u8> {
+    drawing::scale_to_tile_size(board.render_for_spectators())
+        .encode_png()
+        .unwrap()
+}
+
+/// Starts mirroring a game in a channel and posts the board there, drawn by
+/// [`render_for_spectators`].
+pub async fn watch(
+    http: &Http,
+    data: &TypeMap,
+    key: &GameKey,
+    image: Vec<u8>,
+    channel: ChannelId,
+) -> Result<(), serenity::Error> {
+    let message = send_mirror(http, channel, image).await?;
+
+    let spectators = data.get::<SpectatorDataKey>().unwrap();
+    let mut watchers = spectators.entry(key.clone()).or_default();
+
+    watchers.retain(|spectator| spectator.channel != channel.0);
+    watchers.push(Spectator {
+        channel: channel.0,
+        message: Some(message),
+    });
+
+    Ok(())
+}
+
+/// Stops every mirror in a channel, returning whether there were any.
+pub fn unwatch(data: &TypeMap, channel: ChannelId) -> bool {
+    let spectators = data.get::<SpectatorDataKey>().unwrap();
+    let mut removed = false;
+
+    spectators.retain(|_, watchers| {
+        let count = watchers.len();
+        watchers.retain(|spectator| spectator.channel != channel.0);
+        removed |= watchers.len() != count;
+        !watchers.is_empty()
+    });
+
+    removed
+}
+
+/// Brings every mirror of a game up to date after a move. Mirrors are dropped once the game is
+/// over, after showing the final board. The game is only held while it's drawn.
+pub async fn update_spectators(http: &Http, data: &TypeMap, key: &GameKey) {
+    let spectators = data.get::<SpectatorDataKey>().unwrap();
+
+    let watchers = match spectators.get(key).map(|watchers| watchers.clone()) {
+        Some(watchers) => watchers,
+        None => return,
+    };
+
+    let drawn = data
+        .get::<GameDataKey>()
+        .unwrap()
+        .get(key)
+        .map(|board| (render_for_spectators(&**board), board.is_finished()));
+
+    let (image, finished) = match drawn {
+        Some(drawn) => drawn,
+        None => return,
+    };
+    let mut updated = Vec::with_capacity(watchers.len());
+
+    for mut spectator in watchers {
+        let channel = ChannelId(spectator.channel);
+
+        // Only a mirror that's gone is sent again, so a rate limited edit doesn't turn into
+        // another message.
+        let gone = match spectator.message {
+            Some(message) => matches!(
+                board_message::edit_message_image(http, channel, message, image.clone(), vec![])
+                    .await,
+                Err(BoardMessageError::Gone)
+            ),
+            None => true,
+        };
+
+        if gone {
+            spectator.message = send_mirror(http, channel, image.clone()).await.ok();
+        }
+        updated.push(spectator);
+    }
+
+    if finished {
+        spectators.remove(key);
+        return;
+    }
+
+    let watchers = spectators.get_mut(key);
+    if let Some(mut watchers) = watchers {
+        for spectator in watchers.iter_mut() {
+            if let Some(update) = updated
+                .iter()
+                .find(|update| update.channel == spectator.channel)
+            {
+                spectator.message = update.message;
+            }
+        }
+    }
+}
+
+async fn send_mirror(
+    http: &Http,
+    channel: ChannelId,
+    image: Vec<u8>,
+) -> Result<u64, serenity::Error> {
+    let attachment = AttachmentType::Bytes {
+        data: Cow::Owned(image),
+        filename: "File.png".to_string(),
+    };
+
+    let message = channel
+        .send_message(http, |m| m.add_file(attachment))
+        .await?;
+
+    Ok(message.id.0)
+}
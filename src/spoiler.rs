@@ -0,0 +1,145 @@
+use std::fmt;
+
+use crate::game::Game;
+
+pub const MAX_MESSAGE_LENGTH: usize = 2000;
+pub const MAX_EMOJIS_PER_MESSAGE: usize = 99;
+pub const MAX_MESSAGES: usize = 6;
+
+const NUMBER_EMOJIS: [&str; 9] = [
+    ":zero:", ":one:", ":two:", ":three:", ":four:", ":five:", ":six:", ":seven:", ":eight:",
+];
+const MINE_EMOJI: &str = ":bomb:";
+
+#[derive(Debug)]
+pub enum SpoilerBoardError {
+    RowTooLong,
+    TooManyMessages(usize),
+}
+
+impl fmt::Display for SpoilerBoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpoilerBoardError::RowTooLong => {
+                write!(f, "A single row of this board doesn't fit in one message.")
+            }
+            SpoilerBoardError::TooManyMessages(count) => write!(
+                f,
+                "This board would need {} messages, but at most {} are allowed.",
+                count, MAX_MESSAGES
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SpoilerBoardError {}
+
+fn spoiler_row(game: &Game, y: usize) -> String {
+    game.tiles[y]
+        .iter()
+        .map(|tile| {
+            let emoji = if tile.is_mine {
+                MINE_EMOJI
+            } else {
+                NUMBER_EMOJIS[tile.adjacent_mines as usize]
+            };
+            format!("||{}||", emoji)
+        })
+        .collect()
+}
+
+/// Renders every tile as a spoiler-tagged emoji, one board row per line, split into as few
+/// messages as Discord's length and emoji limits allow. Rows are never split across messages.
+pub fn render_spoiler_board(game: &Game) -> Result<Vec<String>, SpoilerBoardError> {
+    let row_emojis = game.width as usize;
+
+    let mut messages = vec![];
+    let mut current = String::new();
+    let mut current_emojis = 0;
+
+    for y in 0..game.height as usize {
+        let row = spoiler_row(game, y);
+
+        if row.len() > MAX_MESSAGE_LENGTH || row_emojis > MAX_EMOJIS_PER_MESSAGE {
+            return Err(SpoilerBoardError::RowTooLong);
+        }
+
+        if current.len() + row.len() + 1 > MAX_MESSAGE_LENGTH
+            || current_emojis + row_emojis > MAX_EMOJIS_PER_MESSAGE
+        {
+            messages.push(std::mem::take(&mut current));
+            current_emojis = 0;
+        }
+
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&row);
+        current_emojis += row_emojis;
+    }
+
+    if !current.is_empty() {
+        messages.push(current);
+    }
+
+    if messages.len() > MAX_MESSAGES {
+        return Err(SpoilerBoardError::TooManyMessages(messages.len()));
+    }
+
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spoiler_board(
+        width: u32,
+        height: u32,
+        mines: u32,
+    ) -> Result<Vec<String>, SpoilerBoardError> {
+        render_spoiler_board(&Game::new_generated(width, height, mines).unwrap())
+    }
+
+    #[test]
+    fn small_boards_fit_in_one_message() {
+        let messages = spoiler_board(9, 9, 10).unwrap();
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].lines().count(), 9);
+        assert_eq!(messages[0].matches(MINE_EMOJI).count(), 10);
+    }
+
+    #[test]
+    fn messages_are_split_between_whole_rows() {
+        let messages = spoiler_board(16, 16, 40).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages
+                .iter()
+                .map(|message| message.lines().count())
+                .sum::<usize>(),
+            16
+        );
+        for message in &messages {
+            assert!(message.len() <= MAX_MESSAGE_LENGTH);
+            assert!(message.matches("||").count() / 2 <= MAX_EMOJIS_PER_MESSAGE);
+            assert!(message
+                .lines()
+                .all(|row| row.matches("||").count() / 2 == 16));
+        }
+    }
+
+    #[test]
+    fn boards_that_need_too_much_are_refused() {
+        assert!(matches!(
+            spoiler_board(100, 2, 10),
+            Err(SpoilerBoardError::RowTooLong)
+        ));
+        assert!(matches!(
+            spoiler_board(30, 30, 100),
+            Err(SpoilerBoardError::TooManyMessages(10))
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use std::{fs, fs::File, io::BufReader, time::SystemTime};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::data;
+
+/// Reads one of the JSON files the bot keeps in its storage directory. A missing file gives the
+/// default. A file that can't be read is logged and moved aside, so the bot still starts and the
+/// old data is kept for someone to look at instead of being overwritten by the next save.
+pub fn load<T: DeserializeOwned + Default>(file_name: &str) -> T {
+    let path = match data::storage_path(file_name) {
+        Ok(path) => path,
+        Err(e) => {
+            log::error!("Couldn't find {}: {}", file_name, e);
+            return T::default();
+        }
+    };
+
+    if !path.exists() {
+        return T::default();
+    }
+
+    let loaded = File::open(&path)
+        .map_err(Box::<dyn std::error::Error>::from)
+        .and_then(|file| Ok(serde_json::from_reader(BufReader::new(file))?));
+
+    match loaded {
+        Ok(value) => value,
+        Err(e) => {
+            let seconds = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let backup = path.with_file_name(format!("{}.{}.bak", file_name, seconds));
+
+            match fs::rename(&path, &backup) {
+                Ok(()) => log::error!(
+                    "Couldn't parse {}, starting without it. It was moved to {}: {}",
+                    file_name,
+                    backup.display(),
+                    e
+                ),
+                Err(rename_error) => log::error!(
+                    "Couldn't parse {}, starting without it. It couldn't be moved aside either ({}): {}",
+                    file_name,
+                    rename_error,
+                    e
+                ),
+            }
+
+            T::default()
+        }
+    }
+}
+
+/// Writes one of the JSON files in the storage directory. The data goes to a temporary file that
+/// then replaces the old one, so a crash halfway through a save can't leave a truncated file.
+pub fn save<T: Serialize>(file_name: &str, value: &T) -> Result<(), Box<dyn std::error::Error>> {
+    let path = data::storage_path(file_name)?;
+    let temporary = path.with_file_name(format!("{}.tmp", file_name));
+
+    serde_json::to_writer(File::create(&temporary)?, value)?;
+    fs::rename(&temporary, &path)?;
+    Ok(())
+}
@@ -1,30 +1,22 @@
-use std::{fs::File, io::Read};
-
 use image::{codecs::png::PngEncoder, DynamicImage, GenericImage, ImageEncoder, Rgba};
 use rusttype::{point, Font, Scale};
 use tiny_skia::Pixmap;
 
 lazy_static! {
     pub static ref ROBOTO: Font<'static> = Font::try_from_vec({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = crate::data::asset_dir();
         path.push("fonts");
         path.push("Roboto-Bold.ttf");
 
-        let file = File::open(path).unwrap();
-        file.bytes().flatten().collect::<Vec<_>>()
+        std::fs::read(path).unwrap()
     })
     .unwrap();
     pub static ref EB_GARAMOND: Font<'static> = Font::try_from_vec({
-        let mut path = std::env::current_exe().unwrap();
-        path.pop();
-        path.push("assets");
+        let mut path = crate::data::asset_dir();
         path.push("fonts");
         path.push("EBGaramond-SemiBold.ttf");
 
-        let file = File::open(path).unwrap();
-        file.bytes().flatten().collect::<Vec<_>>()
+        std::fs::read(path).unwrap()
     })
     .unwrap();
 }